@@ -1,16 +1,343 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 use tauri::{
-    menu::{Menu, MenuItem},
+    menu::{CheckMenuItem, Menu, MenuItem},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
     Emitter, Manager, PhysicalPosition, WebviewUrl, WebviewWindowBuilder,
 };
-use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Modifiers, Shortcut};
+use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Modifiers, Shortcut, ShortcutState};
 use tauri_plugin_autostart::{MacosLauncher, ManagerExt};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_notification::NotificationExt;
+use tauri_plugin_opener::OpenerExt;
 use mouse_position::mouse_position::Mouse;
 
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Error returned by every command. Reaches the frontend as `{ code, message }`
+/// so it can tell e.g. a bad API key apart from being offline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppError {
+    Config(String),
+    Network(String),
+    Auth(String),
+    Shortcut(String),
+    NotFound(String),
+    Window(String),
+    // A value the user supplied that the app can't work with, e.g. an empty prompt
+    InvalidInput(String),
+    Internal(String),
+}
+
+impl AppError {
+    fn code(&self) -> &'static str {
+        match self {
+            AppError::Config(_) => "config",
+            AppError::Network(_) => "network",
+            AppError::Auth(_) => "auth",
+            AppError::Shortcut(_) => "shortcut",
+            AppError::NotFound(_) => "notFound",
+            AppError::Window(_) => "window",
+            AppError::InvalidInput(_) => "invalidInput",
+            AppError::Internal(_) => "internal",
+        }
+    }
+
+    fn message(&self) -> &str {
+        match self {
+            AppError::Config(message)
+            | AppError::Network(message)
+            | AppError::Auth(message)
+            | AppError::Shortcut(message)
+            | AppError::NotFound(message)
+            | AppError::Window(message)
+            | AppError::InvalidInput(message)
+            | AppError::Internal(message) => message,
+        }
+    }
+
+    /// Prefixes the message with `context`, keeping the error code.
+    fn context(self, context: &str) -> Self {
+        let message = format!("{}: {}", context, self.message());
+        match self {
+            AppError::Config(_) => AppError::Config(message),
+            AppError::Network(_) => AppError::Network(message),
+            AppError::Auth(_) => AppError::Auth(message),
+            AppError::Shortcut(_) => AppError::Shortcut(message),
+            AppError::NotFound(_) => AppError::NotFound(message),
+            AppError::Window(_) => AppError::Window(message),
+            AppError::InvalidInput(_) => AppError::InvalidInput(message),
+            AppError::Internal(_) => AppError::Internal(message),
+        }
+    }
+
+    /// A rejected API key comes back as 401/403; everything else is a network problem.
+    fn from_http(context: &str, e: reqwest::Error) -> Self {
+        let message = format!("{}: {}", context, e);
+        match e.status() {
+            Some(reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN) => AppError::Auth(message),
+            _ => AppError::Network(message),
+        }
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<AppError> for tauri::ipc::InvokeError {
+    fn from(error: AppError) -> Self {
+        tauri::ipc::InvokeError::from(serde_json::json!({
+            "code": error.code(),
+            "message": error.message(),
+        }))
+    }
+}
+
+/// Logs and records an error that no command returns to the frontend, e.g.
+/// from a global shortcut, the tray menu or a background task.
+fn report_error(error: AppError) {
+    eprintln!("{}", error);
+    record_recent_error(&error);
+}
+
+/// Runs a command's body and records its error for `get_recent_errors`. Every
+/// fallible command goes through this or `recorded_async`, except
+/// `load_settings`: most code paths call it, and many fall back to defaults.
+fn recorded<T>(command: impl FnOnce() -> Result<T, AppError>) -> Result<T, AppError> {
+    command().inspect_err(record_recent_error)
+}
+
+async fn recorded_async<T>(command: impl std::future::Future<Output = Result<T, AppError>>) -> Result<T, AppError> {
+    command.await.inspect_err(record_recent_error)
+}
+
+const MAX_RECENT_ERRORS: usize = 50;
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentError {
+    pub timestamp: String,
+    pub code: String,
+    pub message: String,
+}
+
+// Newest last, capped at `MAX_RECENT_ERRORS`
+static RECENT_ERRORS: Mutex<std::collections::VecDeque<RecentError>> =
+    Mutex::new(std::collections::VecDeque::new());
+
+fn record_recent_error(error: &AppError) {
+    let mut errors = RECENT_ERRORS.lock().unwrap();
+    // A command that fails inside another command is recorded by both
+    if errors
+        .back()
+        .is_some_and(|last| last.code == error.code() && last.message == error.message())
+    {
+        return;
+    }
+    if errors.len() == MAX_RECENT_ERRORS {
+        errors.pop_front();
+    }
+    errors.push_back(RecentError {
+        timestamp: format_utc_timestamp(SystemTime::now()),
+        code: error.code().to_string(),
+        message: error.message().to_string(),
+    });
+}
+
+/// The errors most recently shown to the user, newest first.
+#[tauri::command]
+fn get_recent_errors() -> Vec<RecentError> {
+    RECENT_ERRORS.lock().unwrap().iter().rev().cloned().collect()
+}
+
+#[tauri::command]
+fn clear_recent_errors() {
+    RECENT_ERRORS.lock().unwrap().clear();
+}
+
+impl From<tauri::Error> for AppError {
+    fn from(e: tauri::Error) -> Self {
+        AppError::Window(e.to_string())
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(e: reqwest::Error) -> Self {
+        AppError::from_http("Request failed", e)
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_app_referer() -> String {
+    "https://github.com/Mekacher-Anis/ai-quick-access".to_string()
+}
+
+fn default_app_title() -> String {
+    "AI Quick Access".to_string()
+}
+
+fn default_double_press_ms() -> u64 {
+    400
+}
+
+fn default_low_credit_threshold() -> f64 {
+    1.0
+}
+
+fn default_max_height_percentage() -> f64 {
+    0.8
+}
+
+fn default_system_prompt_warn_fraction() -> f64 {
+    0.25
+}
+
+fn default_macos_vibrancy_material() -> String {
+    DEFAULT_MACOS_VIBRANCY_MATERIAL.to_string()
+}
+
+fn default_windows_blur_tint() -> [u8; 4] {
+    [18, 18, 18, 125]
+}
+
+fn default_quick_reply_prompt() -> String {
+    "Respond to the following text. Reply with the answer only.".to_string()
+}
+
+/// Where on its monitor the main window is placed when shown or resized.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum WindowAnchor {
+    #[default]
+    Center,
+    TopCenter,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// How hard models that support extended reasoning are asked to think.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
+/// Whether OpenRouter may route to providers that store or train on prompts.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DataCollection {
+    #[default]
+    Allow,
+    Deny,
+}
+
+/// OpenRouter provider routing, sent as the `provider` field of chat requests.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderPrefs {
+    // Providers to try first, in order, e.g. "Anthropic" or "Together"
+    #[serde(default)]
+    pub order: Vec<String>,
+    #[serde(default = "default_true")]
+    pub allow_fallbacks: bool,
+    // Skip providers that would ignore parameters such as `reasoning` or `stop`
+    #[serde(default)]
+    pub require_parameters: bool,
+    #[serde(default)]
+    pub data_collection: DataCollection,
+}
+
+// Requests always prefer the fastest provider; `provider_preferences` only adds to this
+const PROVIDER_SORT: &str = "throughput";
+
+/// The `provider` object of a chat request, in OpenRouter's own (snake_case)
+/// field names.
+fn provider_request_json(prefs: Option<&ProviderPrefs>) -> serde_json::Value {
+    let mut provider = serde_json::json!({ "sort": PROVIDER_SORT });
+    if let Some(prefs) = prefs {
+        provider["allow_fallbacks"] = serde_json::json!(prefs.allow_fallbacks);
+        provider["require_parameters"] = serde_json::json!(prefs.require_parameters);
+        provider["data_collection"] = serde_json::json!(prefs.data_collection);
+        if !prefs.order.is_empty() {
+            provider["order"] = serde_json::json!(prefs.order);
+        }
+    }
+    provider
+}
+
+/// What the main window is sized for: just the input row, or the input row with
+/// the conversation above it.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum LayoutMode {
+    #[default]
+    Input,
+    Conversation,
+}
+
+/// How the main window is brought in front of other windows when summoned.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RaiseStrategy {
+    // Briefly pins the window on top; some Linux window managers flicker or leave it pinned
+    #[default]
+    AlwaysOnTopFlash,
+    RequestUserAttention,
+    // Only focuses the window. Configs written before the rename say "none"
+    #[serde(alias = "none")]
+    Focus,
+}
+
+/// How the app's own files are written. Either is read back the same way.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum StorageFormat {
+    // Indented, for editing by hand
+    #[default]
+    Pretty,
+    Compact,
+}
+
+impl StorageFormat {
+    fn serialize<T: Serialize + ?Sized>(self, value: &T) -> serde_json::Result<String> {
+        match self {
+            StorageFormat::Pretty => serde_json::to_string_pretty(value),
+            StorageFormat::Compact => serde_json::to_string(value),
+        }
+    }
+}
+
+/// How links clicked in AI responses are opened.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum LinkOpenMode {
+    #[default]
+    External,
+    Preview,
+}
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -25,391 +352,4677 @@ pub struct Settings {
     pub model_shortcuts: HashMap<String, String>,
     #[serde(default)]
     pub send_on_enter: bool,
+    #[serde(default = "default_true")]
+    pub follow_mouse_monitor: bool,
+    #[serde(default)]
+    pub compact_mode: bool,
+    #[serde(default = "default_app_referer")]
+    pub app_referer: String,
+    #[serde(default = "default_app_title")]
+    pub app_title: String,
+    #[serde(default = "default_true")]
+    pub show_tray_icon: bool,
+    #[serde(default)]
+    pub confirm_on_quit: bool,
+    #[serde(default)]
+    pub window_anchor: WindowAnchor,
+    #[serde(default)]
+    pub window_corner_radius: Option<f64>,
+    #[serde(default = "default_true")]
+    pub window_shadow: bool,
+    #[serde(default)]
+    pub watch_config: bool,
+    #[serde(default)]
+    pub link_open_mode: LinkOpenMode,
+    #[serde(default)]
+    pub quick_reply_enabled: bool,
+    #[serde(default = "default_quick_reply_prompt")]
+    pub quick_reply_prompt: String,
+    #[serde(default)]
+    pub new_chat_model: Option<String>,
+    #[serde(default = "default_true")]
+    pub preload_window: bool,
+    #[serde(default)]
+    pub reasoning_effort: Option<ReasoningEffort>,
+    #[serde(default)]
+    pub stop_sequences: Vec<String>,
+    #[serde(default)]
+    pub double_press_new_chat: bool,
+    #[serde(default = "default_double_press_ms")]
+    pub double_press_ms: u64,
+    #[serde(default = "default_low_credit_threshold")]
+    pub low_credit_threshold: f64,
+    #[serde(default = "default_macos_vibrancy_material")]
+    pub macos_vibrancy_material: String,
+    // RGBA tint of the blur behind the main window on Windows
+    #[serde(default = "default_windows_blur_tint")]
+    pub windows_blur_tint: [u8; 4],
+    #[serde(default)]
+    pub new_chat_after_idle_mins: Option<u64>,
+    #[serde(default)]
+    pub fallback_models: Vec<String>,
+    // Share of the model's context the system prompt may take before it's flagged
+    #[serde(default = "default_system_prompt_warn_fraction")]
+    pub system_prompt_warn_fraction: f64,
+    // Two models the A/B shortcut flips `selected_model` between
+    #[serde(default)]
+    pub ab_models: Option<(String, String)>,
+    // Overrides the default A/B toggle shortcut (Ctrl/Cmd+Alt+B)
+    #[serde(default)]
+    pub ab_toggle_shortcut: Option<String>,
+    #[serde(default)]
+    pub prefetch_on_launch: bool,
+    // Upper bound on how much of the monitor's height the window may take
+    #[serde(default = "default_max_height_percentage")]
+    pub max_height_percentage: f64,
+    // Saved OpenRouter keys by name; `api_key` always holds the active one's value
+    #[serde(default)]
+    pub named_api_keys: HashMap<String, String>,
+    #[serde(default)]
+    pub active_key_name: Option<String>,
+    // Plain-text file every completed chat is appended to
+    #[serde(default)]
+    pub archive_to_file: Option<String>,
+    // Program (plus arguments) run with each reply on stdin. Not run through a shell.
+    #[serde(default)]
+    pub on_complete_command: Option<String>,
+    #[serde(default)]
+    pub provider_preferences: Option<ProviderPrefs>,
+    // Global shortcuts switched on or off by name (see `SHORTCUT_NAMES`); missing
+    // means on, except for `OPT_IN_SHORTCUTS`
+    #[serde(default)]
+    pub shortcut_enabled: HashMap<String, bool>,
+    #[serde(default)]
+    pub raise_strategy: RaiseStrategy,
+    // `#RRGGBB` for buttons and highlights; empty keeps the theme's own color
+    #[serde(default)]
+    pub accent_color: String,
+    // Markdown/text file used instead of `system_prompt` while it exists
+    #[serde(default)]
+    pub system_prompt_file: Option<String>,
+    // Login launches pass `--hidden` and stay in the tray; manual starts always open the window
+    #[serde(default = "default_true")]
+    pub start_hidden: bool,
+    #[serde(default)]
+    pub storage_format: StorageFormat,
+    #[serde(default)]
+    pub suspend_shortcuts_in_fullscreen: bool,
+    // Extra headers sent with every API request, e.g. `OpenAI-Organization`
+    #[serde(default)]
+    pub custom_headers: HashMap<String, String>,
+    // Linux only: pull the window onto the active virtual desktop when shown
+    #[serde(default = "default_true")]
+    pub summon_on_current_workspace: bool,
+    // Short names for model ids, e.g. "gpt" -> "openai/gpt-oss-120b"
+    #[serde(default)]
+    pub model_aliases: HashMap<String, String>,
+}
+
+/// Runtime state of the main window that isn't worth persisting to disk.
+#[derive(Default)]
+struct WindowState {
+    last_monitor: Mutex<Option<String>>,
+    // Bumped by every resize so an in-flight animation can tell it was superseded
+    resize_generation: AtomicU64,
+    // While on, the window stays pinned above other windows
+    do_not_disturb: AtomicBool,
+    // While on, clicks pass through the window to whatever is underneath
+    click_through: AtomicBool,
+    // When the focus shortcut last fired, for double-press detection
+    last_focus_press: Mutex<Option<Instant>>,
+    // Last message sent or focus change of the main window
+    last_interaction: Mutex<Option<Instant>>,
+    layout_mode: Mutex<LayoutMode>,
+    // Outcome of applying vibrancy/blur to the main window, once it was built
+    effects_support: Mutex<Option<EffectsSupport>>,
+    // Bumped by every move/resize so only the last one in a burst is reported
+    geometry_event_generation: AtomicU64,
+    // Global shortcuts are unregistered while a fullscreen app is in front
+    shortcuts_suspended: AtomicBool,
+    // Mirrors `suspend_shortcuts_in_fullscreen` so the poller needn't read the config
+    suspend_in_fullscreen: AtomicBool,
+    // Wakes the fullscreen poller, which sleeps while `suspend_in_fullscreen` is off
+    fullscreen_poller_wake: tokio::sync::Notify,
+}
+
+/// Tray menu items whose state mirrors a setting and has to be kept in sync.
+struct TrayMenuState {
+    compact_mode_item: CheckMenuItem<tauri::Wry>,
+    do_not_disturb_item: CheckMenuItem<tauri::Wry>,
+    click_through_item: CheckMenuItem<tauri::Wry>,
+}
+
+/// HTTP client shared by every command that talks to the network, so requests
+/// reuse pooled keep-alive connections instead of handshaking each time.
+struct HttpClient(reqwest::Client);
+
+/// What's known about every OpenRouter model, by model id. Filled the first time
+/// the model list is fetched.
+#[derive(Default)]
+struct ModelCapabilities(Mutex<HashMap<String, ModelMetadata>>);
+
+/// When startup milestones were reached, for `get_startup_timings`.
+struct StartupTimings {
+    process_start: Instant,
+    setup_done: Mutex<Option<Instant>>,
+    first_show: Mutex<Option<Instant>>,
+}
+
+struct ModelMetadata {
+    supported_parameters: Vec<String>,
+    context_length: Option<u64>,
+}
+
+const OPENROUTER_BASE_URL: &str = "https://openrouter.ai/api/v1";
+const CONNECTIVITY_TIMEOUT: Duration = Duration::from_secs(3);
+const HTTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const HTTP_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+const HTTP_POOL_MAX_IDLE_PER_HOST: usize = 4;
+
+fn build_http_client(app: &tauri::AppHandle) -> Result<reqwest::Client, String> {
+    let user_agent = format!("ai-quick-access/{}", app.package_info().version);
+
+    reqwest::Client::builder()
+        .user_agent(user_agent)
+        .connect_timeout(HTTP_CONNECT_TIMEOUT)
+        .pool_idle_timeout(HTTP_POOL_IDLE_TIMEOUT)
+        .pool_max_idle_per_host(HTTP_POOL_MAX_IDLE_PER_HOST)
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))
+}
+
+// Corner radius of the vibrancy layer on macOS when the user hasn't picked one
+#[cfg(target_os = "macos")]
+const DEFAULT_WINDOW_CORNER_RADIUS: f64 = 10.0;
+
+const DEFAULT_MACOS_VIBRANCY_MATERIAL: &str = "UnderWindowBackground";
+
+// Names of the non-deprecated `NSVisualEffectMaterial` variants
+const MACOS_VIBRANCY_MATERIALS: &[&str] = &[
+    "Titlebar",
+    "Selection",
+    "Menu",
+    "Popover",
+    "Sidebar",
+    "HeaderView",
+    "Sheet",
+    "WindowBackground",
+    "HudWindow",
+    "FullScreenUI",
+    "Tooltip",
+    "ContentBackground",
+    "UnderWindowBackground",
+    "UnderPageBackground",
+];
+
+/// Maps a `macos_vibrancy_material` setting to its material. Unknown names fall
+/// back to the default rather than failing, so a hand-edited config still loads.
+#[cfg(target_os = "macos")]
+fn macos_vibrancy_material(name: &str) -> window_vibrancy::NSVisualEffectMaterial {
+    use window_vibrancy::NSVisualEffectMaterial as Material;
+
+    match name {
+        "Titlebar" => Material::Titlebar,
+        "Selection" => Material::Selection,
+        "Menu" => Material::Menu,
+        "Popover" => Material::Popover,
+        "Sidebar" => Material::Sidebar,
+        "HeaderView" => Material::HeaderView,
+        "Sheet" => Material::Sheet,
+        "WindowBackground" => Material::WindowBackground,
+        "HudWindow" => Material::HudWindow,
+        "FullScreenUI" => Material::FullScreenUI,
+        "Tooltip" => Material::Tooltip,
+        "ContentBackground" => Material::ContentBackground,
+        "UnderPageBackground" => Material::UnderPageBackground,
+        "UnderWindowBackground" => Material::UnderWindowBackground,
+        _ => {
+            eprintln!("Unknown vibrancy material '{}', using {}", name, DEFAULT_MACOS_VIBRANCY_MATERIAL);
+            Material::UnderWindowBackground
+        }
+    }
+}
+
+const MAIN_WINDOW_TITLE: &str = "AI Quick Access";
+const MAX_WINDOW_TITLE_CHARS: usize = 80;
+
+const DEFAULT_WINDOW_HEIGHT: f64 = 150.0;
+const COMPACT_WINDOW_HEIGHT: f64 = 100.0;
+
+fn default_settings() -> Settings {
+    let mut default_shortcuts = HashMap::new();
+    default_shortcuts.insert("h".to_string(), "google/gemini-3-pro-preview".to_string());
+    default_shortcuts.insert("f".to_string(), "google/gemini-3-flash-preview".to_string());
+    default_shortcuts.insert("o".to_string(), "openai/gpt-oss-120b".to_string());
+    Settings {
+        api_key: String::new(),
+        selected_model: "openai/gpt-oss-120b".to_string(),
+        dark_mode: true,
+        auto_start: false,
+        system_prompt: "Keep your responses as concise, precise, to the point.\nAnswer the question in as few words as possible.\nNo Yapping.".to_string(),
+        model_shortcuts: default_shortcuts,
+        send_on_enter: false,
+        follow_mouse_monitor: true,
+        compact_mode: false,
+        app_referer: default_app_referer(),
+        app_title: default_app_title(),
+        show_tray_icon: true,
+        confirm_on_quit: false,
+        window_anchor: WindowAnchor::Center,
+        window_corner_radius: None,
+        window_shadow: true,
+        watch_config: false,
+        link_open_mode: LinkOpenMode::External,
+        quick_reply_enabled: false,
+        quick_reply_prompt: default_quick_reply_prompt(),
+        new_chat_model: None,
+        preload_window: true,
+        reasoning_effort: None,
+        stop_sequences: Vec::new(),
+        double_press_new_chat: false,
+        double_press_ms: default_double_press_ms(),
+        low_credit_threshold: default_low_credit_threshold(),
+        macos_vibrancy_material: default_macos_vibrancy_material(),
+        windows_blur_tint: default_windows_blur_tint(),
+        new_chat_after_idle_mins: None,
+        fallback_models: Vec::new(),
+        system_prompt_warn_fraction: default_system_prompt_warn_fraction(),
+        ab_models: None,
+        ab_toggle_shortcut: None,
+        prefetch_on_launch: false,
+        max_height_percentage: default_max_height_percentage(),
+        named_api_keys: HashMap::new(),
+        active_key_name: None,
+        archive_to_file: None,
+        on_complete_command: None,
+        provider_preferences: None,
+        shortcut_enabled: HashMap::new(),
+        raise_strategy: RaiseStrategy::default(),
+        accent_color: String::new(),
+        system_prompt_file: None,
+        start_hidden: true,
+        storage_format: StorageFormat::default(),
+        suspend_shortcuts_in_fullscreen: false,
+        custom_headers: HashMap::new(),
+        summon_on_current_workspace: true,
+        model_aliases: HashMap::new(),
+    }
+}
+
+fn get_default_config_dir() -> Result<PathBuf, AppError> {
+    let config_dir = dirs::config_dir().ok_or_else(|| AppError::Config("Could not find config directory".to_string()))?;
+    Ok(config_dir.join("ai-quick-access"))
+}
+
+// Tiny bootstrap file in the default config directory holding the path of a
+// user-chosen config directory (e.g. on a synced drive).
+fn get_config_dir_override_path() -> Result<PathBuf, AppError> {
+    Ok(get_default_config_dir()?.join("config-location"))
+}
+
+fn get_config_dir_override() -> Option<PathBuf> {
+    let contents = fs::read_to_string(get_config_dir_override_path().ok()?).ok()?;
+    let dir = contents.trim();
+    (!dir.is_empty()).then(|| PathBuf::from(dir))
+}
+
+fn get_config_path() -> Result<PathBuf, AppError> {
+    let app_config_dir = match get_config_dir_override() {
+        Some(dir) => dir,
+        None => get_default_config_dir()?,
+    };
+    Ok(app_config_dir.join("configs.json"))
+}
+
+// Write to a sibling temp file first so a crash never leaves a half-written file
+fn write_file_atomically(path: &std::path::Path, contents: &str) -> Result<(), AppError> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, contents).map_err(|e| AppError::Config(format!("Failed to write {}: {}", tmp_path.display(), e)))?;
+    fs::rename(&tmp_path, path).map_err(|e| AppError::Config(format!("Failed to move {} into place: {}", path.display(), e)))
+}
+
+/// Moves `configs.json` and its fallback copy to `path` and remembers that
+/// location for future launches. Fails if `path` already holds a config, rather
+/// than overwriting it. A config file watcher keeps watching the old location
+/// until restart.
+#[tauri::command]
+fn set_config_directory(path: String) -> Result<(), AppError> {
+    recorded(|| {
+        let new_dir = PathBuf::from(path.trim());
+        if !new_dir.is_absolute() {
+            return Err(AppError::Config(format!("Config directory '{}' must be an absolute path", path.trim())));
+        }
+
+        fs::create_dir_all(&new_dir).map_err(|e| AppError::Config(format!("Failed to create {}: {}", new_dir.display(), e)))?;
+        let probe_path = new_dir.join(".write-test");
+        fs::write(&probe_path, "").map_err(|e| AppError::Config(format!("{} is not writable: {}", new_dir.display(), e)))?;
+        let _ = fs::remove_file(&probe_path);
+
+        let old_path = get_config_path()?;
+        let new_path = new_dir.join("configs.json");
+        if old_path == new_path {
+            return Ok(());
+        }
+        // The fallback copy may be the newest settings, so it moves along
+        let moves = [
+            (old_path.clone(), new_path.clone()),
+            (get_fallback_config_path()?, new_path.with_file_name("configs.fallback.json")),
+        ];
+        if let Some((_, existing)) = moves.iter().find(|(_, to)| to.exists()) {
+            return Err(AppError::Config(format!(
+                "{} already exists; move or remove it before using this directory",
+                existing.display()
+            )));
+        }
+
+        for (from, to) in moves.iter().filter(|(from, _)| from.exists()) {
+            let contents = fs::read_to_string(from)
+                .map_err(|e| AppError::Config(format!("Failed to read {}: {}", from.display(), e)))?;
+            write_file_atomically(to, &contents)?;
+        }
+
+        // Only switch over once the config is safely in its new home
+        let override_path = get_config_dir_override_path()?;
+        if new_dir == get_default_config_dir()? {
+            if override_path.exists() {
+                fs::remove_file(&override_path).map_err(|e| AppError::Config(format!("Failed to reset config location: {}", e)))?;
+            }
+        } else {
+            if let Some(parent) = override_path.parent() {
+                fs::create_dir_all(parent).map_err(|e| AppError::Config(format!("Failed to create config directory: {}", e)))?;
+            }
+            write_file_atomically(&override_path, &new_dir.to_string_lossy())?;
+        }
+
+        for (from, _) in &moves {
+            let _ = fs::remove_file(from);
+        }
+        Ok(())
+    })
+}
+
+// The nearest existing ancestor of `dir`, if that turns out to be a file rather
+// than a directory. `create_dir_all` only reports a cryptic OS error for this.
+fn file_blocking_dir(dir: &std::path::Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|path| path.exists())
+        .filter(|path| !path.is_dir())
+        .map(PathBuf::from)
+}
+
+fn ensure_config_dir() -> Result<(), AppError> {
+    let config_path = get_config_path()?;
+    if let Some(parent) = config_path.parent() {
+        if let Some(file) = file_blocking_dir(parent) {
+            return Err(AppError::Config(format!(
+                "A file exists where the config directory should be: {}",
+                file.display()
+            )));
+        }
+        fs::create_dir_all(parent).map_err(|e| AppError::Config(format!("Failed to create config directory: {}", e)))?;
+    }
+    Ok(())
+}
+
+/// Copies the full path of `configs.json` to the clipboard, for support requests.
+#[tauri::command]
+fn copy_config_path(app: tauri::AppHandle) -> Result<(), AppError> {
+    recorded(|| {
+        let path = get_config_path()?;
+        app.clipboard()
+            .write_text(path.to_string_lossy().into_owned())
+            .map_err(|e| AppError::Internal(format!("Failed to write clipboard: {}", e)))
+    })
+}
+
+/// Renames a file sitting where the config directory should be out of the way
+/// and creates the directory. Returns where the file was moved, if anywhere.
+#[tauri::command]
+fn repair_config_dir() -> Result<Option<String>, AppError> {
+    recorded(|| {
+        let config_path = get_config_path()?;
+        let config_dir = config_path
+            .parent()
+            .ok_or_else(|| AppError::Config("Config path has no parent directory".to_string()))?;
+
+        let moved_to = match file_blocking_dir(config_dir) {
+            Some(file) => {
+                let mut backup = file.with_extension("bak");
+                let mut attempt = 1;
+                while backup.exists() {
+                    attempt += 1;
+                    backup = file.with_extension(format!("bak{}", attempt));
+                }
+                fs::rename(&file, &backup)
+                    .map_err(|e| AppError::Config(format!("Failed to move {} aside: {}", file.display(), e)))?;
+                Some(backup.to_string_lossy().into_owned())
+            }
+            None => None,
+        };
+
+        ensure_config_dir()?;
+        Ok(moved_to)
+    })
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestCheck {
+    pub name: String,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+impl SelfTestCheck {
+    fn from_result(name: &str, result: Result<(), AppError>) -> Self {
+        SelfTestCheck {
+            name: name.to_string(),
+            ok: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
+        }
+    }
+}
+
+/// Results of the checks run once at startup, so "it won't save my key" reports
+/// on locked-down machines can be diagnosed from the settings page.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StartupDiagnostics {
+    pub checks: Vec<SelfTestCheck>,
+    pub all_passed: bool,
+}
+
+fn check_config_dir_round_trip() -> Result<(), AppError> {
+    let config_path = get_config_path()?;
+    let test_path = config_path.with_file_name(".write-test");
+    let probe = "ai-quick-access write test";
+
+    fs::write(&test_path, probe).map_err(|e| AppError::Config(format!("Failed to write {}: {}", test_path.display(), e)))?;
+    let read_back = fs::read_to_string(&test_path)
+        .map_err(|e| AppError::Config(format!("Failed to read {}: {}", test_path.display(), e)))?;
+    fs::remove_file(&test_path).map_err(|e| AppError::Config(format!("Failed to delete {}: {}", test_path.display(), e)))?;
+
+    if read_back != probe {
+        return Err(AppError::Config(format!("{} did not read back what was written", test_path.display())));
+    }
+    Ok(())
+}
+
+/// What the window system lets the app do. Wayland compositors ignore
+/// `set_position` and don't let apps grab global shortcuts.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PlatformCapabilities {
+    pub os: String,
+    /// "wayland" or "x11" on Linux, `None` elsewhere
+    pub display_server: Option<String>,
+    pub window_positioning: bool,
+    pub global_shortcuts: bool,
+}
+
+fn detect_display_server() -> Option<String> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    // GDK_BACKEND=x11 runs the app through XWayland, where X11 rules apply
+    if std::env::var("GDK_BACKEND").is_ok_and(|backend| backend == "x11") {
+        return Some("x11".to_string());
+    }
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some()
+        || std::env::var("XDG_SESSION_TYPE").is_ok_and(|session| session == "wayland");
+    Some(if wayland { "wayland" } else { "x11" }.to_string())
+}
+
+fn platform_capabilities() -> PlatformCapabilities {
+    let display_server = detect_display_server();
+    let wayland = display_server.as_deref() == Some("wayland");
+
+    PlatformCapabilities {
+        os: std::env::consts::OS.to_string(),
+        display_server,
+        window_positioning: !wayland,
+        global_shortcuts: !wayland,
+    }
+}
+
+#[tauri::command]
+fn get_platform_capabilities() -> PlatformCapabilities {
+    platform_capabilities()
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectsSupport {
+    pub vibrancy: bool,
+    pub blur: bool,
+    // False until the main window has been built and the effect actually tried
+    pub probed: bool,
+    pub error: Option<String>,
+}
+
+fn record_effects_support(app: &tauri::AppHandle, support: EffectsSupport) {
+    if let Some(error) = &support.error {
+        eprintln!("Window effects unavailable: {}", error);
+    }
+    *app.state::<WindowState>().effects_support.lock().unwrap() = Some(support);
+}
+
+/// Whether macOS vibrancy or Windows blur actually works here. The answer comes
+/// from applying the effect to the main window, since blur depends on the
+/// Windows build rather than just the OS. Before that window exists, the OS
+/// decides and `probed` is false.
+#[tauri::command]
+fn get_effects_support(window_state: tauri::State<'_, WindowState>) -> EffectsSupport {
+    if let Some(support) = window_state.effects_support.lock().unwrap().clone() {
+        return support;
+    }
+    EffectsSupport {
+        vibrancy: cfg!(target_os = "macos"),
+        blur: cfg!(target_os = "windows"),
+        // Nothing to try elsewhere: neither effect exists there
+        probed: !cfg!(any(target_os = "macos", target_os = "windows")),
+        error: None,
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StartupTimingsReport {
+    // Both measured from process start; `None` until reached
+    pub setup_ms: Option<u64>,
+    pub first_show_ms: Option<u64>,
+}
+
+fn startup_timings_report(timings: &StartupTimings) -> StartupTimingsReport {
+    let since_start = |at: &Mutex<Option<Instant>>| {
+        at.lock()
+            .unwrap()
+            .map(|at| at.duration_since(timings.process_start).as_millis() as u64)
+    };
+    StartupTimingsReport {
+        setup_ms: since_start(&timings.setup_done),
+        first_show_ms: since_start(&timings.first_show),
+    }
+}
+
+/// Records that the main window was shown, the first time only.
+fn mark_first_show(app: &tauri::AppHandle) {
+    let timings = app.state::<StartupTimings>();
+    let mut first_show = timings.first_show.lock().unwrap();
+    if first_show.is_none() {
+        *first_show = Some(Instant::now());
+        drop(first_show);
+        if let Some(ms) = startup_timings_report(&timings).first_show_ms {
+            eprintln!("Main window first shown {}ms after start", ms);
+        }
+    }
+}
+
+#[tauri::command]
+fn get_startup_timings(timings: tauri::State<'_, StartupTimings>) -> StartupTimingsReport {
+    startup_timings_report(&timings)
+}
+
+#[tauri::command]
+fn get_startup_diagnostics(diagnostics: tauri::State<'_, StartupDiagnostics>) -> StartupDiagnostics {
+    diagnostics.inner().clone()
+}
+
+fn sync_launch_at_startup(app: &tauri::AppHandle, enable: bool) -> Result<(), AppError> {
+    let autolaunch = app.autolaunch();
+    let currently_enabled = autolaunch
+        .is_enabled()
+        .map_err(|e| AppError::Config(format!("Failed to read launch at startup state: {}", e)))?;
+
+    if enable && !currently_enabled {
+        autolaunch
+            .enable()
+            .map_err(|e| AppError::Config(format!("Failed to enable launch at startup: {}", e)))?;
+        record_autostart_registration(app);
+    } else if !enable && currently_enabled {
+        autolaunch
+            .disable()
+            .map_err(|e| AppError::Config(format!("Failed to disable launch at startup: {}", e)))?;
+    }
+
+    Ok(())
+}
+
+const HIDDEN_ARG: &str = "--hidden";
+
+/// Arguments the login item launches the app with.
+fn autostart_args(start_hidden: bool) -> Vec<&'static str> {
+    if start_hidden {
+        vec![HIDDEN_ARG]
+    } else {
+        Vec::new()
+    }
+}
+
+#[tauri::command]
+fn get_autostart_args() -> Result<Vec<String>, AppError> {
+    recorded(|| {
+        let settings = load_settings()?;
+        Ok(autostart_args(settings.start_hidden).into_iter().map(String::from).collect())
+    })
+}
+
+/// Arguments the autostart plugin was initialized with. The plugin fixes them
+/// when the app starts, so a `start_hidden` change is registered on the next start.
+struct AutostartArgs(Vec<&'static str>);
+
+// Bootstrap file in the default config directory holding the arguments the
+// login item was last registered with, so startup only rewrites it on a change.
+fn get_autostart_record_path() -> Result<PathBuf, AppError> {
+    Ok(get_default_config_dir()?.join("autostart-args"))
+}
+
+fn record_autostart_registration(app: &tauri::AppHandle) {
+    let args = app.state::<AutostartArgs>().0.join(" ");
+    if let Err(e) = get_autostart_record_path().and_then(|path| write_file_atomically(&path, &args)) {
+        eprintln!("Failed to record launch at startup arguments: {}", e);
+    }
+}
+
+/// Rewrites the login item when it was registered with other arguments than
+/// this launch's, e.g. after `start_hidden` changed during the previous run.
+fn refresh_autostart_registration(app: &tauri::AppHandle, auto_start: bool) -> Result<(), AppError> {
+    if !auto_start {
+        return Ok(());
+    }
+    let args = app.state::<AutostartArgs>().0.join(" ");
+    let registered = get_autostart_record_path().ok().and_then(|path| fs::read_to_string(path).ok());
+    if registered.as_deref().map(str::trim) == Some(args.as_str()) {
+        return Ok(());
+    }
+    let autolaunch = app.autolaunch();
+    let _ = autolaunch.disable();
+    autolaunch
+        .enable()
+        .map_err(|e| AppError::Config(format!("Failed to re-register launch at startup: {}", e)))?;
+    record_autostart_registration(app);
+    Ok(())
+}
+
+/// Removes `//` and `/* */` comments and commas right before `}` or `]`, which
+/// hand-edited config files often contain. Strings are left untouched. The app
+/// itself always writes strict JSON.
+fn strip_json_leniency(contents: &str) -> String {
+    let mut output = String::with_capacity(contents.len());
+    let mut chars = contents.chars().peekable();
+    // Index in `output` of a comma that may turn out to be trailing
+    let mut pending_comma: Option<usize> = None;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                pending_comma = None;
+                output.push(c);
+                while let Some(c) = chars.next() {
+                    output.push(c);
+                    match c {
+                        '\\' => output.extend(chars.next()),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = '\0';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            ',' => {
+                pending_comma = Some(output.len());
+                output.push(c);
+            }
+            '}' | ']' => {
+                if let Some(index) = pending_comma.take() {
+                    output.remove(index);
+                }
+                output.push(c);
+            }
+            c if c.is_whitespace() => output.push(c),
+            _ => {
+                pending_comma = None;
+                output.push(c);
+            }
+        }
+    }
+    output
+}
+
+/// Reads the settings, tolerating comments and trailing commas in the file
+/// (see `strip_json_leniency`).
+#[tauri::command]
+fn load_settings() -> Result<Settings, AppError> {
+    let config_path = newest_config_path()?;
+    
+    if !config_path.exists() {
+        // Return default settings if file doesn't exist
+        return Ok(default_settings());
+    }
+    
+    let contents = fs::read_to_string(&config_path)
+        .map_err(|e| AppError::Config(format!("Failed to read config file: {}", e)))?;
+    
+    let mut settings: Settings = serde_json::from_str(&contents)
+        .or_else(|_| serde_json::from_str(&strip_json_leniency(&contents)))
+        .map_err(|e| AppError::Config(format!("Failed to parse config file: {}", e)))?;
+    migrate_api_keys(&mut settings);
+    Ok(settings)
+}
+
+const DEFAULT_API_KEY_NAME: &str = "default";
+
+/// Files a single `api_key` from before named keys existed as the "default" key.
+fn migrate_api_keys(settings: &mut Settings) {
+    if settings.named_api_keys.is_empty() && !settings.api_key.is_empty() {
+        settings
+            .named_api_keys
+            .insert(DEFAULT_API_KEY_NAME.to_string(), settings.api_key.clone());
+        settings.active_key_name = Some(DEFAULT_API_KEY_NAME.to_string());
+    }
+}
+
+// Hash of what the app itself last wrote to configs.json, so the config watcher
+// can tell its own writes apart from external edits.
+static LAST_WRITTEN_CONFIG_HASH: Mutex<Option<u64>> = Mutex::new(None);
+
+const CONFIG_WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+fn hash_config_contents(contents: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+const CONFIG_WRITE_ATTEMPTS: u32 = 3;
+const CONFIG_WRITE_RETRY_DELAY: Duration = Duration::from_millis(150);
+
+// Where settings go when `configs.json` itself stays locked
+fn get_fallback_config_path() -> Result<PathBuf, AppError> {
+    Ok(get_config_path()?.with_file_name("configs.fallback.json"))
+}
+
+/// `configs.json`, unless a fallback write left a newer copy next to it.
+fn newest_config_path() -> Result<PathBuf, AppError> {
+    let primary = get_config_path()?;
+    let fallback = get_fallback_config_path()?;
+    let modified = |path: &PathBuf| fs::metadata(path).and_then(|m| m.modified()).ok();
+
+    match (modified(&primary), modified(&fallback)) {
+        (Some(primary_time), Some(fallback_time)) if fallback_time > primary_time => Ok(fallback),
+        (None, Some(_)) => Ok(fallback),
+        _ => Ok(primary),
+    }
+}
+
+// Antivirus scanners and sync clients briefly hold files open on Windows. Any
+// other failure, e.g. a read-only config, is reported rather than retried.
+fn is_transient_lock_error(e: &std::io::Error) -> bool {
+    // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+    cfg!(windows) && matches!(e.raw_os_error(), Some(32) | Some(33))
+}
+
+fn write_settings(settings: &Settings) -> Result<(), AppError> {
+    write_settings_with_fallback(settings).map(|_| ())
+}
+
+/// Writes `configs.json`, retrying while it is locked. If it stays locked the
+/// settings go to the fallback file instead, whose path is returned. The
+/// retries sleep, so only call this off the main thread (async commands).
+fn write_settings_with_fallback(settings: &Settings) -> Result<Option<PathBuf>, AppError> {
+    ensure_config_dir()?;
+    let config_path = get_config_path()?;
+    let fallback_path = get_fallback_config_path()?;
+
+    let contents = settings
+        .storage_format
+        .serialize(settings)
+        .map_err(|e| AppError::Config(format!("Failed to serialize settings: {}", e)))?;
+
+    *LAST_WRITTEN_CONFIG_HASH.lock().unwrap() = Some(hash_config_contents(&contents));
+    let mut attempt = 1;
+    let error = loop {
+        match fs::write(&config_path, &contents) {
+            Ok(()) => {
+                // The primary is current again, so an older fallback must not win on load
+                let _ = fs::remove_file(&fallback_path);
+                return Ok(None);
+            }
+            Err(e) if is_transient_lock_error(&e) && attempt < CONFIG_WRITE_ATTEMPTS => {
+                attempt += 1;
+                std::thread::sleep(CONFIG_WRITE_RETRY_DELAY);
+            }
+            Err(e) => break e,
+        }
+    };
+    if !is_transient_lock_error(&error) {
+        return Err(AppError::Config(format!("Failed to write config file: {}", error)));
+    }
+
+    fs::write(&fallback_path, &contents).map_err(|e| {
+        AppError::Config(format!(
+            "Failed to write config file ({}) and fallback config file: {}",
+            error, e
+        ))
+    })?;
+    eprintln!(
+        "Config file is locked ({}); saved settings to {} instead",
+        error,
+        fallback_path.display()
+    );
+    Ok(Some(fallback_path))
+}
+
+/// The running config file watcher, while `watch_config` is on. Dropping it
+/// stops the watch and ends its reload thread.
+#[derive(Default)]
+struct ConfigWatcher(Mutex<Option<notify::RecommendedWatcher>>);
+
+/// Starts or stops the config file watcher to match `watch_config`.
+fn sync_config_watcher(app: &tauri::AppHandle, enabled: bool) -> Result<(), AppError> {
+    let state = app.state::<ConfigWatcher>();
+    let mut watcher = state.0.lock().unwrap();
+    if !enabled {
+        *watcher = None;
+    } else if watcher.is_none() {
+        *watcher = Some(watch_config_file(app)?);
+    }
+    Ok(())
+}
+
+/// Watches `configs.json` and reloads settings when something other than the
+/// app changes it. Bursts of events (editors often write several times) are
+/// debounced into a single reload.
+fn watch_config_file(app: &tauri::AppHandle) -> Result<notify::RecommendedWatcher, AppError> {
+    use notify::Watcher;
+
+    let config_path = get_config_path()?;
+    let config_dir = config_path.parent().ok_or_else(|| AppError::Config("Config path has no parent directory".to_string()))?.to_path_buf();
+
+    let (tx, rx) = std::sync::mpsc::channel::<notify::Result<notify::Event>>();
+    let mut watcher = notify::recommended_watcher(tx)
+        .map_err(|e| AppError::Config(format!("Failed to create config watcher: {}", e)))?;
+    // Watch the directory: editors that save via rename replace the file itself
+    watcher
+        .watch(&config_dir, notify::RecursiveMode::NonRecursive)
+        .map_err(|e| AppError::Config(format!("Failed to watch config directory: {}", e)))?;
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let touches_config = |event: &notify::Result<notify::Event>| {
+            event
+                .as_ref()
+                .is_ok_and(|e| e.paths.iter().any(|p| p.file_name() == config_path.file_name()))
+        };
+
+        while let Ok(event) = rx.recv() {
+            if !touches_config(&event) {
+                continue;
+            }
+            while rx.recv_timeout(CONFIG_WATCH_DEBOUNCE).is_ok() {}
+
+            let contents = match fs::read_to_string(&config_path) {
+                Ok(contents) => contents,
+                Err(_) => continue,
+            };
+            let own_write = *LAST_WRITTEN_CONFIG_HASH.lock().unwrap() == Some(hash_config_contents(&contents));
+            let still_enabled = load_settings().map(|s| s.watch_config).unwrap_or(false);
+            if own_write || !still_enabled {
+                continue;
+            }
+
+            if let Err(e) = reload_settings(app.clone()) {
+                eprintln!("Failed to reload changed config file: {}", e);
+            }
+        }
+    });
+
+    Ok(watcher)
+}
+
+// OpenRouter only attributes requests whose HTTP-Referer is an absolute URL.
+fn validate_app_referer(referer: &str) -> Result<(), AppError> {
+    let referer = referer.trim();
+    if referer.is_empty() {
+        return Ok(());
+    }
+
+    let url = tauri::Url::parse(referer)
+        .map_err(|e| AppError::Config(format!("Invalid app referer '{}': {}", referer, e)))?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(AppError::Config(format!("Invalid app referer '{}': expected an http(s) URL", referer)));
+    }
+    Ok(())
+}
+
+// Most providers behind OpenRouter reject more stop sequences than this
+const MAX_STOP_SEQUENCES: usize = 4;
+
+fn normalize_stop_sequences(sequences: &[String]) -> Result<Vec<String>, AppError> {
+    let sequences: Vec<String> = sequences
+        .iter()
+        .filter(|sequence| !sequence.trim().is_empty())
+        .cloned()
+        .collect();
+    if sequences.len() > MAX_STOP_SEQUENCES {
+        return Err(AppError::Config(format!(
+            "At most {} stop sequences are supported, got {}",
+            MAX_STOP_SEQUENCES,
+            sequences.len()
+        )));
+    }
+    Ok(sequences)
+}
+
+/// Trims provider names and rejects ones OpenRouter couldn't know. The model list
+/// doesn't say which providers serve a model, so only the names' shape is checked.
+fn normalize_provider_preferences(prefs: &mut ProviderPrefs) -> Result<(), AppError> {
+    prefs.order = prefs
+        .order
+        .iter()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect();
+    let allowed = |c: char| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.' | '/');
+    if let Some(name) = prefs.order.iter().find(|name| !name.chars().all(allowed)) {
+        return Err(AppError::Config(format!("'{}' is not a valid provider name", name)));
+    }
+    Ok(())
+}
+
+// Set by the app itself, from the API key, the request or their own settings
+const RESERVED_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "host",
+    "content-type",
+    "content-length",
+    "http-referer",
+    "x-title",
+];
+
+/// Trims header names and values, drops blank names and rejects headers that
+/// aren't valid HTTP or would override one the app sets.
+fn normalize_custom_headers(headers: &HashMap<String, String>) -> Result<HashMap<String, String>, AppError> {
+    let mut normalized = HashMap::new();
+    for (name, value) in headers {
+        let name = name.trim();
+        if name.is_empty() {
+            continue;
+        }
+        let value = value.trim();
+        if reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_err() {
+            return Err(AppError::Config(format!("'{}' is not a valid header name", name)));
+        }
+        if reqwest::header::HeaderValue::from_str(value).is_err() {
+            return Err(AppError::Config(format!("The value of header '{}' is not valid", name)));
+        }
+        if RESERVED_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+            return Err(AppError::Config(format!("Header '{}' is set by the app and can't be overridden", name)));
+        }
+        normalized.insert(name.to_string(), value.to_string());
+    }
+    Ok(normalized)
+}
+
+// Whether `model` may be a real model id. Anything goes until the model list
+// has been fetched.
+fn is_known_model(capabilities: &ModelCapabilities, model: &str) -> bool {
+    let known_models = capabilities.0.lock().unwrap();
+    known_models.is_empty() || known_models.contains_key(model)
+}
+
+/// Lowercases and trims aliases, drops blank ones and, once the model list has
+/// been fetched, rejects aliases pointing at models that don't exist.
+fn normalize_model_aliases(
+    aliases: &HashMap<String, String>,
+    capabilities: &ModelCapabilities,
+) -> Result<HashMap<String, String>, AppError> {
+    let mut normalized = HashMap::new();
+    for (alias, model) in aliases {
+        let (alias, model) = (alias.trim().to_lowercase(), model.trim());
+        if alias.is_empty() || model.is_empty() {
+            continue;
+        }
+        if !is_known_model(capabilities, model) {
+            return Err(AppError::Config(format!("Alias '{}' points to unknown model '{}'", alias, model)));
+        }
+        normalized.insert(alias, model.to_string());
+    }
+    Ok(normalized)
+}
+
+/// The model id `alias_or_id` stands for. Anything that isn't an alias is
+/// assumed to be an id already.
+fn resolve_model_id(settings: &Settings, alias_or_id: &str) -> String {
+    let alias_or_id = alias_or_id.trim();
+    settings
+        .model_aliases
+        .get(&alias_or_id.to_lowercase())
+        .cloned()
+        .unwrap_or_else(|| alias_or_id.to_string())
+}
+
+#[tauri::command]
+fn resolve_model(alias_or_id: String) -> Result<String, AppError> {
+    recorded(|| {
+        Ok(resolve_model_id(&load_settings()?, &alias_or_id))
+    })
+}
+
+/// Accepts `#RRGGBB` (or empty, for the theme default) and returns it uppercased.
+fn normalize_accent_color(color: &str) -> Result<String, AppError> {
+    let color = color.trim();
+    if color.is_empty() {
+        return Ok(String::new());
+    }
+    let valid = color.len() == 7
+        && color.starts_with('#')
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+    if !valid {
+        return Err(AppError::Config(format!("Accent color '{}' must look like #RRGGBB", color)));
+    }
+    Ok(color.to_ascii_uppercase())
+}
+
+#[tauri::command]
+async fn set_accent_color(app: tauri::AppHandle, color: String) -> Result<(), AppError> {
+    recorded_async(async move {
+        let mut settings = load_settings()?;
+        settings.accent_color = normalize_accent_color(&color)?;
+        write_settings(&settings)?;
+        app.emit("settings-changed", &settings).map_err(AppError::from)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn save_settings(app: tauri::AppHandle, mut settings: Settings) -> Result<(), AppError> {
+    recorded_async(async move {
+        validate_app_referer(&settings.app_referer)?;
+        settings.accent_color = normalize_accent_color(&settings.accent_color)?;
+        if !MACOS_VIBRANCY_MATERIALS.contains(&settings.macos_vibrancy_material.as_str()) {
+            settings.macos_vibrancy_material = default_macos_vibrancy_material();
+        }
+        if !(settings.max_height_percentage > 0.0 && settings.max_height_percentage <= 1.0) {
+            settings.max_height_percentage = default_max_height_percentage();
+        }
+        if !(settings.system_prompt_warn_fraction > 0.0 && settings.system_prompt_warn_fraction <= 1.0) {
+            settings.system_prompt_warn_fraction = default_system_prompt_warn_fraction();
+        }
+        settings.stop_sequences = normalize_stop_sequences(&settings.stop_sequences)?;
+        settings.custom_headers = normalize_custom_headers(&settings.custom_headers)?;
+        settings.model_aliases = normalize_model_aliases(&settings.model_aliases, &app.state::<ModelCapabilities>())?;
+        if let Some(prefs) = settings.provider_preferences.as_mut() {
+            normalize_provider_preferences(prefs)?;
+        }
+        // Editing the key field edits whichever named key is active
+        migrate_api_keys(&mut settings);
+        if let Some(active) = &settings.active_key_name {
+            if !settings.api_key.is_empty() {
+                settings.named_api_keys.insert(active.clone(), settings.api_key.clone());
+            }
+        }
+        settings.ab_toggle_shortcut = match settings.ab_toggle_shortcut.take().filter(|s| !s.trim().is_empty()) {
+            Some(accelerator) => Some(validate_shortcut(accelerator)?),
+            None => None,
+        };
+        // An empty pick in the UI means "use the selected model"
+        settings.new_chat_model = settings
+            .new_chat_model
+            .take()
+            .map(|model| model.trim().to_string())
+            .filter(|model| !model.is_empty());
+        if let Some(model) = &settings.new_chat_model {
+            if !is_known_model(&app.state::<ModelCapabilities>(), &resolve_model_id(&settings, model)) {
+                return Err(AppError::Config(format!("Model for new chats '{}' is not a known model", model)));
+            }
+        }
+        settings.system_prompt_file = settings
+            .system_prompt_file
+            .take()
+            .map(|path| path.trim().to_string())
+            .filter(|path| !path.is_empty());
+        settings.archive_to_file = settings
+            .archive_to_file
+            .take()
+            .map(|path| path.trim().to_string())
+            .filter(|path| !path.is_empty());
+        settings.on_complete_command = settings
+            .on_complete_command
+            .take()
+            .map(|command| command.trim().to_string())
+            .filter(|command| !command.is_empty());
+        if let Some(command) = &settings.on_complete_command {
+            split_command_line(command)?;
+        }
+        if let Some(fallback_path) = write_settings_with_fallback(&settings)? {
+            let _ = app.emit("config-write-fallback", fallback_path.display().to_string());
+        }
+
+        sync_launch_at_startup(&app, settings.auto_start)?;
+        sync_compact_mode_item(&app, settings.compact_mode);
+        sync_suspend_in_fullscreen(&app, settings.suspend_shortcuts_in_fullscreen);
+        // The settings are saved either way, so a watcher that won't start is only reported
+        if let Err(e) = sync_config_watcher(&app, settings.watch_config) {
+            report_error(e.context("Failed to update config file watching"));
+        }
+        // Picks up changed or newly enabled shortcuts, e.g. quick reply or the A/B toggle
+        reregister_global_shortcuts(&app);
+        let _ = app.emit("settings-changed", &settings);
+
+        Ok(())
+    })
+    .await
+}
+
+fn sync_compact_mode_item(app: &tauri::AppHandle, enabled: bool) {
+    if let Some(tray_menu) = app.try_state::<TrayMenuState>() {
+        let _ = tray_menu.compact_mode_item.set_checked(enabled);
+    }
+}
+
+fn sync_suspend_in_fullscreen(app: &tauri::AppHandle, enabled: bool) {
+    let state = app.state::<WindowState>();
+    state.suspend_in_fullscreen.store(enabled, Ordering::SeqCst);
+    if enabled {
+        state.fullscreen_poller_wake.notify_one();
+    }
+}
+
+fn default_window_height() -> f64 {
+    let compact = load_settings().map(|s| s.compact_mode).unwrap_or(false);
+    if compact {
+        COMPACT_WINDOW_HEIGHT
+    } else {
+        DEFAULT_WINDOW_HEIGHT
+    }
+}
+
+#[tauri::command]
+async fn set_compact_mode(app: tauri::AppHandle, enabled: bool) -> Result<(), AppError> {
+    recorded_async(async move {
+        let mut settings = load_settings()?;
+        settings.compact_mode = enabled;
+        write_settings(&settings)?;
+
+        sync_compact_mode_item(&app, enabled);
+        app.emit("settings-changed", &settings).map_err(AppError::from)
+    })
+    .await
+}
+
+const ENCRYPTED_SETTINGS_VERSION: u32 = 1;
+const ENCRYPTED_SETTINGS_SALT_LEN: usize = 16;
+
+/// On-disk container for an encrypted settings export. Binary fields are base64.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EncryptedSettings {
+    version: u32,
+    kdf: String,
+    cipher: String,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+fn derive_settings_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], AppError> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| AppError::Config(format!("Failed to derive key from passphrase: {}", e)))?;
+    Ok(key)
+}
+
+/// Encrypts `settings` with a key derived from `passphrase` (Argon2id + AES-256-GCM).
+fn encrypt_settings(settings: &Settings, passphrase: &str) -> Result<EncryptedSettings, AppError> {
+    if passphrase.is_empty() {
+        return Err(AppError::Config("Passphrase cannot be empty".to_string()));
+    }
+
+    let plaintext = serde_json::to_vec(settings)
+        .map_err(|e| AppError::Config(format!("Failed to serialize settings: {}", e)))?;
+
+    let mut salt = [0u8; ENCRYPTED_SETTINGS_SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_settings_key(passphrase, &salt)?;
+
+    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|e| AppError::Internal(e.to_string()))?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_slice())
+        .map_err(|e| AppError::Config(format!("Failed to encrypt settings: {}", e)))?;
+
+    Ok(EncryptedSettings {
+        version: ENCRYPTED_SETTINGS_VERSION,
+        kdf: "argon2id".to_string(),
+        cipher: "aes-256-gcm".to_string(),
+        salt: BASE64.encode(salt),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    })
+}
+
+/// Reverses `encrypt_settings`.
+fn decrypt_settings(container: &EncryptedSettings, passphrase: &str) -> Result<Settings, AppError> {
+    if container.version != ENCRYPTED_SETTINGS_VERSION {
+        return Err(AppError::Config(format!("Unsupported encrypted settings version {}", container.version)));
+    }
+
+    let decode = |field: &str, value: &str| {
+        BASE64
+            .decode(value)
+            .map_err(|e| AppError::Config(format!("Corrupted export file ({}): {}", field, e)))
+    };
+    let salt = decode("salt", &container.salt)?;
+    let nonce = decode("nonce", &container.nonce)?;
+    let ciphertext = decode("ciphertext", &container.ciphertext)?;
+    if nonce.len() != 12 {
+        return Err(AppError::Config("Corrupted export file (nonce): wrong length".to_string()));
+    }
+
+    let key = derive_settings_key(passphrase, &salt)?;
+    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|e| AppError::Internal(e.to_string()))?;
+    // GCM authentication fails the same way for a wrong passphrase and for tampering
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| AppError::Auth("Wrong passphrase or corrupted export file".to_string()))?;
+
+    serde_json::from_slice(&plaintext)
+        .map_err(|e| AppError::Config(format!("Failed to parse decrypted settings: {}", e)))
+}
+
+/// Writes the current settings, API key included, encrypted with a key derived
+/// from `passphrase` (see `encrypt_settings`).
+#[tauri::command]
+fn export_settings_encrypted(path: String, passphrase: String) -> Result<(), AppError> {
+    recorded(|| {
+        let container = encrypt_settings(&load_settings()?, &passphrase)?;
+        let contents = serde_json::to_string_pretty(&container)
+            .map_err(|e| AppError::Config(format!("Failed to serialize encrypted settings: {}", e)))?;
+
+        fs::write(&path, contents).map_err(|e| AppError::Config(format!("Failed to write export file: {}", e)))
+    })
+}
+
+#[tauri::command]
+async fn import_settings_encrypted(app: tauri::AppHandle, path: String, passphrase: String) -> Result<Settings, AppError> {
+    recorded_async(async move {
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| AppError::Config(format!("Failed to read export file: {}", e)))?;
+        let container: EncryptedSettings = serde_json::from_str(&contents)
+            .map_err(|e| AppError::Config(format!("Not an encrypted settings export: {}", e)))?;
+        let settings = decrypt_settings(&container, &passphrase)?;
+
+        save_settings(app, settings.clone()).await?;
+        Ok(settings)
+    })
+    .await
+}
+
+const STATE_SNAPSHOT_VERSION: u32 = 1;
+
+/// Everything needed to put the app back into the state a bug was seen in.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StateSnapshot {
+    pub version: u32,
+    pub settings: Settings,
+    // The chat window's messages, as handed over by the frontend
+    #[serde(default)]
+    pub conversation: Option<serde_json::Value>,
+    #[serde(default)]
+    pub window_geometry: Option<WindowGeometry>,
+    #[serde(default)]
+    pub recent_prompts: Vec<String>,
+    #[serde(default)]
+    pub scratch: String,
+    #[serde(default)]
+    pub do_not_disturb: bool,
+    #[serde(default)]
+    pub click_through: bool,
+}
+
+/// Writes settings, the given conversation, window geometry and runtime state to
+/// `path` as one JSON file. API keys are redacted unless `include_api_key` is set.
+#[tauri::command]
+fn export_state_snapshot(
+    app: tauri::AppHandle,
+    path: String,
+    include_api_key: bool,
+    conversation: Option<serde_json::Value>,
+) -> Result<(), AppError> {
+    recorded(|| {
+        let mut settings = load_settings()?;
+        if !include_api_key {
+            settings.api_key = redact_secret(&settings.api_key);
+            for key in settings.named_api_keys.values_mut() {
+                *key = redact_secret(key);
+            }
+        }
+
+        let snapshot = StateSnapshot {
+            version: STATE_SNAPSHOT_VERSION,
+            settings,
+            conversation,
+            window_geometry: app
+            .get_webview_window("main")
+            .and_then(|window| window_geometry(&window.as_ref().window()).ok()),
+            recent_prompts: get_recent_prompts(app.clone())?,
+            scratch: load_scratch(app.clone())?,
+            do_not_disturb: is_do_not_disturb(&app),
+            click_through: is_click_through(&app),
+        };
+
+        let contents = serde_json::to_string_pretty(&snapshot)
+            .map_err(|e| AppError::Internal(format!("Failed to serialize state snapshot: {}", e)))?;
+        fs::write(&path, contents).map_err(|e| AppError::Config(format!("Failed to write state snapshot: {}", e)))
+    })
+}
+
+/// Restores a snapshot written by `export_state_snapshot`. Redacted API keys
+/// keep the current ones. Click-through is left off so the window stays usable;
+/// the snapshot's conversation is handed to the chat window via `restore-conversation`.
+#[tauri::command]
+async fn import_state_snapshot(app: tauri::AppHandle, path: String) -> Result<StateSnapshot, AppError> {
+    recorded_async(async move {
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| AppError::Config(format!("Failed to read state snapshot: {}", e)))?;
+        let snapshot: StateSnapshot = serde_json::from_str(&contents)
+            .map_err(|e| AppError::Config(format!("Not a state snapshot: {}", e)))?;
+        if snapshot.version != STATE_SNAPSHOT_VERSION {
+            return Err(AppError::Config(format!("Unsupported state snapshot version {}", snapshot.version)));
+        }
+
+        let current = load_settings()?;
+        let mut settings = snapshot.settings.clone();
+        // A redacted (or empty) key redacts to itself
+        if settings.api_key == redact_secret(&settings.api_key) {
+            settings.api_key = current.api_key;
+            settings.named_api_keys = current.named_api_keys;
+            settings.active_key_name = current.active_key_name;
+        }
+        // Applies and announces the settings too
+        save_settings(app.clone(), settings).await?;
+
+        write_recent_prompts(&app, &snapshot.recent_prompts)?;
+        save_scratch(app.clone(), snapshot.scratch.clone())?;
+        set_do_not_disturb(app.clone(), snapshot.do_not_disturb)?;
+
+        if let (Some(geometry), Some(window)) = (&snapshot.window_geometry, app.get_webview_window("main")) {
+            window.set_size(tauri::PhysicalSize::new(geometry.width, geometry.height))?;
+            window.set_position(PhysicalPosition::new(geometry.x, geometry.y))?;
+        }
+        if let Some(conversation) = &snapshot.conversation {
+            app.emit("restore-conversation", conversation)?;
+        }
+
+        Ok(snapshot)
+    })
+    .await
+}
+
+// Combos the OS (or the user's muscle memory) already owns. Compared against the
+// canonical form produced by `shortcut_to_string`.
+const RESERVED_SHORTCUTS: &[&str] = &[
+    "Control+Alt+Delete",
+    "Alt+F4",
+    "Alt+Tab",
+    "Super+Tab",
+    "Super+KeyQ",
+    "Super+Space",
+    "Super+KeyL",
+];
+
+fn normalize_modifier_token(token: &str) -> &str {
+    // The global-hotkey parser understands Cmd/Command/Super/Option/CmdOrCtrl, but
+    // not the names Windows and Linux users tend to type.
+    match token.to_lowercase().as_str() {
+        "win" | "windows" | "meta" => "Super",
+        "ctl" => "Control",
+        _ => token,
+    }
+}
+
+fn shortcut_to_string(shortcut: &Shortcut) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    if shortcut.mods.contains(Modifiers::CONTROL) {
+        parts.push("Control".to_string());
+    }
+    if shortcut.mods.contains(Modifiers::ALT) {
+        parts.push("Alt".to_string());
+    }
+    if shortcut.mods.contains(Modifiers::SHIFT) {
+        parts.push("Shift".to_string());
+    }
+    if shortcut.mods.contains(Modifiers::SUPER) {
+        parts.push("Super".to_string());
+    }
+    parts.push(shortcut.key.to_string());
+    parts.join("+")
+}
+
+fn parse_shortcut(accelerator: &str) -> Result<Shortcut, AppError> {
+    let accelerator = accelerator.trim();
+    if accelerator.is_empty() {
+        return Err(AppError::Shortcut("Shortcut cannot be empty".to_string()));
+    }
+
+    let tokens: Vec<&str> = accelerator.split('+').map(|t| t.trim()).collect();
+    if tokens.iter().any(|t| t.is_empty()) {
+        return Err(AppError::Shortcut(format!("Invalid shortcut '{}': empty key between '+' separators", accelerator)));
+    }
+
+    let normalized = tokens
+        .iter()
+        .map(|t| normalize_modifier_token(t))
+        .collect::<Vec<_>>()
+        .join("+");
+
+    normalized
+        .parse::<Shortcut>()
+        .map_err(|e| AppError::Shortcut(format!("Invalid shortcut '{}': {}", accelerator, e)))
+}
+
+#[tauri::command]
+fn validate_shortcut(accelerator: String) -> Result<String, AppError> {
+    recorded(|| {
+        let shortcut = parse_shortcut(&accelerator)?;
+
+        if shortcut.mods.is_empty() {
+            return Err(AppError::Shortcut(format!(
+                "Shortcut '{}' needs at least one modifier (Ctrl, Alt, Shift or Cmd/Super)",
+                accelerator.trim()
+            )));
+        }
+
+        let key_name = shortcut.key.to_string();
+        let is_printable = key_name.starts_with("Key") || key_name.starts_with("Digit");
+        if shortcut.mods == Modifiers::SHIFT && is_printable {
+            return Err(AppError::Shortcut(format!(
+                "Shortcut '{}' would block typing: Shift alone only changes the character",
+                accelerator.trim()
+            )));
+        }
+
+        let canonical = shortcut_to_string(&shortcut);
+        if RESERVED_SHORTCUTS.contains(&canonical.as_str()) {
+            return Err(AppError::Shortcut(format!("Shortcut '{}' is reserved by the system", canonical)));
+        }
+
+        Ok(canonical)
+    })
+}
+
+// Chat commands handled before model shortcuts, e.g. "/s" for web search
+const RESERVED_CHAT_COMMANDS: &[&str] = &["s"];
+const SEND_KEY: &str = "enter";
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ShortcutConflict {
+    pub key: String,
+    pub conflicts_with: String,
+    pub reason: String,
+}
+
+// Model shortcut keys are typed as "/key" and matched by the chat window's
+// `/\w+` prefix, so anything else in a key is never recognised.
+fn find_shortcut_conflicts(model_shortcuts: &HashMap<String, String>) -> Vec<ShortcutConflict> {
+    let mut keys: Vec<&String> = model_shortcuts.keys().collect();
+    keys.sort();
+
+    let mut conflicts = Vec::new();
+    let mut seen: HashMap<String, &String> = HashMap::new();
+    for key in keys {
+        let folded = key.trim().to_lowercase();
+        if folded.is_empty() || !folded.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            conflicts.push(ShortcutConflict {
+                key: key.clone(),
+                conflicts_with: String::new(),
+                reason: "Shortcuts can only contain letters, digits and underscores".to_string(),
+            });
+            continue;
+        }
+        if RESERVED_CHAT_COMMANDS.contains(&folded.as_str()) {
+            conflicts.push(ShortcutConflict {
+                key: key.clone(),
+                conflicts_with: format!("/{}", folded),
+                reason: "Reserved for a built-in chat command".to_string(),
+            });
+        }
+        if folded == SEND_KEY {
+            conflicts.push(ShortcutConflict {
+                key: key.clone(),
+                conflicts_with: "Enter".to_string(),
+                reason: "Same name as the send key".to_string(),
+            });
+        }
+        if let Some(other) = seen.get(&folded) {
+            conflicts.push(ShortcutConflict {
+                key: key.clone(),
+                conflicts_with: (*other).clone(),
+                reason: "Differs from another shortcut only by case".to_string(),
+            });
+        } else {
+            seen.insert(folded, key);
+        }
+    }
+    conflicts
+}
+
+/// Lists model shortcut keys that can't be used as typed, so settings can flag
+/// them before saving. Comparisons ignore case.
+#[tauri::command]
+fn check_shortcut_conflicts(model_shortcuts: HashMap<String, String>) -> Vec<ShortcutConflict> {
+    find_shortcut_conflicts(&model_shortcuts)
+}
+
+/// Human-readable name of `code`, e.g. "E" for `KeyE` or "↑" on macOS for `ArrowUp`.
+fn key_display_name(code: &Code, macos: bool) -> String {
+    let name = code.to_string();
+    if let Some(letter) = name.strip_prefix("Key") {
+        return letter.to_string();
+    }
+    if let Some(digit) = name.strip_prefix("Digit") {
+        return digit.to_string();
+    }
+    if let Some(digit) = name.strip_prefix("Numpad").filter(|d| d.len() == 1) {
+        return format!("Num{}", digit);
+    }
+
+    let mac_symbol = match name.as_str() {
+        "ArrowUp" => Some("↑"),
+        "ArrowDown" => Some("↓"),
+        "ArrowLeft" => Some("←"),
+        "ArrowRight" => Some("→"),
+        "Enter" => Some("↩"),
+        "Backspace" => Some("⌫"),
+        "Delete" => Some("⌦"),
+        "Escape" => Some("⎋"),
+        "Tab" => Some("⇥"),
+        _ => None,
+    };
+    if let Some(symbol) = mac_symbol.filter(|_| macos) {
+        return symbol.to_string();
+    }
+
+    match name.as_str() {
+        "ArrowUp" => "Up",
+        "ArrowDown" => "Down",
+        "ArrowLeft" => "Left",
+        "ArrowRight" => "Right",
+        "Escape" => "Esc",
+        "Delete" => "Del",
+        "PageUp" => "PgUp",
+        "PageDown" => "PgDn",
+        "Backquote" => "`",
+        "Minus" => "-",
+        "Equal" => "=",
+        "BracketLeft" => "[",
+        "BracketRight" => "]",
+        "Backslash" => "\\",
+        "Semicolon" => ";",
+        "Quote" => "'",
+        "Comma" => ",",
+        "Period" => ".",
+        "Slash" => "/",
+        // Function keys (F1-F24), Space, Home, End, ... read fine as they are
+        other => other,
+    }
+    .to_string()
+}
+
+/// Renders `shortcut` the way the current platform writes it: "⌃⌥⇧⌘E" on
+/// macOS, "Ctrl+Alt+Shift+Win+E" on Windows and "Ctrl+Alt+Shift+Super+E" on Linux.
+fn shortcut_display_string(shortcut: &Shortcut) -> String {
+    let macos = cfg!(target_os = "macos");
+    let super_name = if cfg!(target_os = "windows") { "Win" } else { "Super" };
+    let modifiers = [
+        (Modifiers::CONTROL, "⌃", "Ctrl"),
+        (Modifiers::ALT, "⌥", "Alt"),
+        (Modifiers::SHIFT, "⇧", "Shift"),
+        (Modifiers::SUPER, "⌘", super_name),
+    ];
+
+    let mut parts: Vec<String> = modifiers
+        .iter()
+        .filter(|(modifier, _, _)| shortcut.mods.contains(*modifier))
+        .map(|(_, symbol, name)| if macos { symbol } else { name }.to_string())
+        .collect();
+    parts.push(key_display_name(&shortcut.key, macos));
+    parts.join(if macos { "" } else { "+" })
+}
+
+/// Lets the frontend show a binding without knowing each platform's conventions.
+/// Accepts anything `validate_shortcut` does.
+#[tauri::command]
+fn format_shortcut_for_display(accelerator: String) -> Result<String, AppError> {
+    recorded(|| {
+        Ok(shortcut_display_string(&parse_shortcut(&accelerator)?))
+    })
+}
+
+/// The global shortcuts the app registers. The primary modifier is Cmd on macOS
+/// and Ctrl everywhere else.
+struct DefaultShortcuts {
+    focus: Shortcut,
+    new_chat: Shortcut,
+    scratch: Shortcut,
+    append_to_chat: Shortcut,
+    quick_reply: Shortcut,
+    click_through: Shortcut,
+    ab_toggle: Shortcut,
+}
+
+fn default_shortcuts() -> DefaultShortcuts {
+    #[cfg(target_os = "macos")]
+    let mod_key = Modifiers::SUPER;
+    #[cfg(not(target_os = "macos"))]
+    let mod_key = Modifiers::CONTROL;
+
+    DefaultShortcuts {
+        focus: Shortcut::new(Some(mod_key), Code::KeyE),
+        new_chat: Shortcut::new(Some(mod_key | Modifiers::SHIFT), Code::KeyE),
+        scratch: Shortcut::new(Some(mod_key | Modifiers::ALT), Code::KeyE),
+        append_to_chat: Shortcut::new(Some(mod_key | Modifiers::ALT), Code::KeyV),
+        quick_reply: Shortcut::new(Some(mod_key | Modifiers::ALT), Code::KeyR),
+        click_through: Shortcut::new(Some(mod_key | Modifiers::ALT), Code::KeyT),
+        ab_toggle: Shortcut::new(Some(mod_key | Modifiers::ALT), Code::KeyB),
+    }
+}
+
+/// The A/B toggle shortcut from the settings, or the default one if unset or invalid.
+fn ab_toggle_shortcut(settings: &Settings) -> Shortcut {
+    settings
+        .ab_toggle_shortcut
+        .as_deref()
+        .and_then(|accelerator| parse_shortcut(accelerator).ok())
+        .unwrap_or_else(|| default_shortcuts().ab_toggle)
+}
+
+#[tauri::command]
+fn get_default_shortcuts() -> HashMap<String, String> {
+    let defaults = default_shortcuts();
+    let mut shortcuts = HashMap::new();
+    shortcuts.insert("focus".to_string(), shortcut_to_string(&defaults.focus));
+    shortcuts.insert("newChat".to_string(), shortcut_to_string(&defaults.new_chat));
+    shortcuts.insert("scratch".to_string(), shortcut_to_string(&defaults.scratch));
+    shortcuts.insert("appendToChat".to_string(), shortcut_to_string(&defaults.append_to_chat));
+    shortcuts.insert("quickReply".to_string(), shortcut_to_string(&defaults.quick_reply));
+    shortcuts.insert("clickThrough".to_string(), shortcut_to_string(&defaults.click_through));
+    shortcuts.insert("abToggle".to_string(), shortcut_to_string(&defaults.ab_toggle));
+    shortcuts
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectivityStatus {
+    pub online: bool,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+#[tauri::command]
+async fn check_connectivity(client: tauri::State<'_, HttpClient>) -> Result<ConnectivityStatus, AppError> {
+    recorded_async(async move {
+        let started = Instant::now();
+        let response = client
+            .0
+            .head(OPENROUTER_BASE_URL)
+            .timeout(CONNECTIVITY_TIMEOUT)
+            .send()
+            .await;
+
+        match response {
+            // Any HTTP response, even an error status, means the API is reachable
+            Ok(_) => Ok(ConnectivityStatus {
+                online: true,
+                latency_ms: Some(started.elapsed().as_millis() as u64),
+                error: None,
+            }),
+            // DNS failures surface as connect errors
+            Err(e) if e.is_connect() || e.is_timeout() => Ok(ConnectivityStatus {
+                online: false,
+                latency_ms: None,
+                error: Some(e.to_string()),
+            }),
+            Err(e) => Err(AppError::from_http("Failed to check connectivity", e)),
+        }
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenRouterList<T> {
+    data: T,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenRouterModel {
+    id: String,
+    name: String,
+    pricing: OpenRouterPricing,
+    #[serde(default)]
+    supported_parameters: Vec<String>,
+    #[serde(default)]
+    context_length: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenRouterPricing {
+    prompt: String,
+    completion: String,
+}
+
+impl OpenRouterPricing {
+    fn is_free(&self) -> bool {
+        let free = |price: &str| price.parse::<f64>().map(|p| p == 0.0).unwrap_or(false);
+        free(&self.prompt) && free(&self.completion)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenRouterKeyInfo {
+    #[serde(default)]
+    is_free_tier: bool,
+    limit_remaining: Option<f64>,
+    #[serde(default)]
+    usage: Option<f64>,
+}
+
+impl OpenRouterKeyInfo {
+    fn can_use_paid_models(&self) -> bool {
+        !self.is_free_tier && self.limit_remaining.is_none_or(|remaining| remaining > 0.0)
+    }
+}
+
+/// Credit balance of an API key. `remaining` is `None` for keys without a limit.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Credits {
+    pub remaining: Option<f64>,
+    pub used: Option<f64>,
+}
+
+const CREDITS_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// The conversation most recently wiped by a new chat, with when that happened,
+/// so an accidental new chat can be undone.
+#[derive(Default)]
+struct ClearedConversation(Mutex<Option<(Instant, serde_json::Value)>>);
+
+const CLEARED_CONVERSATION_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// System prompt set with `set_session_system_prompt`. Never saved, so it is
+/// gone after a restart; the chat window clears it on new chat.
+#[derive(Default)]
+struct SessionSystemPrompt(Mutex<Option<String>>);
+
+/// Contents of `system_prompt_file`, re-read only when the file changes.
+#[derive(Default)]
+struct SystemPromptCache(Mutex<Option<CachedSystemPrompt>>);
+
+struct CachedSystemPrompt {
+    path: PathBuf,
+    modified: SystemTime,
+    content: String,
+}
+
+// Larger prompt files are cut off; they'd eat most of any model's context anyway
+const MAX_SYSTEM_PROMPT_FILE_BYTES: usize = 64 * 1024;
+
+/// Last credit balance fetched, so every sent message doesn't cost a request.
+#[derive(Default)]
+struct CreditsCache(Mutex<Option<CachedCredits>>);
+
+struct CachedCredits {
+    api_key: String,
+    fetched_at: Instant,
+    credits: Option<Credits>,
+}
+
+/// Remaining and used credits of `api_key`, cached for a minute. Returns `None`
+/// when the key info doesn't report credits. Emits `low-credits` whenever the
+/// balance is below `low_credit_threshold`.
+#[tauri::command]
+async fn get_credits(
+    app: tauri::AppHandle,
+    client: tauri::State<'_, HttpClient>,
+    cache: tauri::State<'_, CreditsCache>,
+    api_key: String,
+) -> Result<Option<Credits>, AppError> {
+    recorded_async(async move {
+        let cached = cache
+            .0
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|cached| cached.api_key == api_key && cached.fetched_at.elapsed() < CREDITS_CACHE_TTL)
+            .map(|cached| cached.credits.clone());
+
+        let credits = match cached {
+            Some(credits) => credits,
+            None => {
+                let key_info = openrouter_get::<OpenRouterList<OpenRouterKeyInfo>>(&client.0, &api_key, "/key")
+                    .await?
+                    .data;
+                let credits = (key_info.limit_remaining.is_some() || key_info.usage.is_some()).then(|| Credits {
+                    remaining: key_info.limit_remaining,
+                    used: key_info.usage,
+                });
+                *cache.0.lock().unwrap() = Some(CachedCredits {
+                    api_key,
+                    fetched_at: Instant::now(),
+                    credits: credits.clone(),
+                });
+                credits
+            }
+        };
+
+        let threshold = load_settings()
+            .map(|s| s.low_credit_threshold)
+            .unwrap_or_else(|_| default_low_credit_threshold());
+        if let Some(remaining) = credits.as_ref().and_then(|c| c.remaining) {
+            if remaining < threshold {
+                let _ = app.emit("low-credits", remaining);
+            }
+        }
+
+        Ok(credits)
+    })
+    .await
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AvailableModel {
+    pub id: String,
+    pub name: String,
+    pub requires_credits: bool,
+}
+
+fn openrouter_request(
+    client: &reqwest::Client,
+    method: reqwest::Method,
+    api_key: &str,
+    path: &str,
+) -> reqwest::RequestBuilder {
+    let settings = load_settings().unwrap_or_else(|_| default_settings());
+    let mut request = client
+        .request(method, format!("{}{}", OPENROUTER_BASE_URL, path))
+        .bearer_auth(api_key);
+    // OpenRouter app attribution
+    if !settings.app_referer.is_empty() {
+        request = request.header("HTTP-Referer", settings.app_referer);
+    }
+    if !settings.app_title.is_empty() {
+        request = request.header("X-Title", settings.app_title);
+    }
+    // Only ever saved after `normalize_custom_headers`, but the file can be hand-edited
+    for (name, value) in &settings.custom_headers {
+        if !RESERVED_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+            request = request.header(name, value);
+        }
+    }
+    request
+}
+
+async fn openrouter_get<T: serde::de::DeserializeOwned>(
+    client: &reqwest::Client,
+    api_key: &str,
+    path: &str,
+) -> Result<T, AppError> {
+    let response = openrouter_request(client, reqwest::Method::GET, api_key, path)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| AppError::from_http(&format!("Request to {} failed", path), e))?;
+    response
+        .json::<T>()
+        .await
+        .map_err(|e| AppError::Network(format!("Failed to parse response from {}: {}", path, e)))
+}
+
+fn remember_model_capabilities(capabilities: &ModelCapabilities, models: &[OpenRouterModel]) {
+    let mut cache = capabilities.0.lock().unwrap();
+    for model in models {
+        cache.insert(
+            model.id.clone(),
+            ModelMetadata {
+                supported_parameters: model.supported_parameters.clone(),
+                context_length: model.context_length,
+            },
+        );
+    }
+}
+
+/// Whether `model` accepts OpenRouter's `reasoning` parameter. If the model list
+/// can't be fetched the model is treated as unsupported, so the parameter is
+/// simply left out.
+async fn supports_reasoning(
+    client: &reqwest::Client,
+    capabilities: &ModelCapabilities,
+    api_key: &str,
+    model: &str,
+) -> bool {
+    // Web search is a suffix on the model id, not a separate model
+    let model = model.strip_suffix(":online").unwrap_or(model);
+
+    let unknown = capabilities.0.lock().unwrap().is_empty();
+    if unknown {
+        match openrouter_get::<OpenRouterList<Vec<OpenRouterModel>>>(client, api_key, "/models").await {
+            Ok(models) => remember_model_capabilities(capabilities, &models.data),
+            Err(e) => {
+                eprintln!("Failed to fetch model capabilities: {}", e);
+                return false;
+            }
+        }
+    }
+
+    capabilities
+        .0
+        .lock()
+        .unwrap()
+        .get(model)
+        .is_some_and(|meta| meta.supported_parameters.iter().any(|p| p == "reasoning"))
+}
+
+/// Fills the model metadata cache in the background so the first chat doesn't
+/// wait on `/models`. Failures are only logged.
+fn prefetch_models(app: &tauri::AppHandle, api_key: String) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let client = app.state::<HttpClient>().0.clone();
+        match openrouter_get::<OpenRouterList<Vec<OpenRouterModel>>>(&client, &api_key, "/models").await {
+            Ok(models) => {
+                remember_model_capabilities(&app.state::<ModelCapabilities>(), &models.data);
+                eprintln!("Prefetched {} models", models.data.len());
+            }
+            Err(e) => eprintln!("Failed to prefetch models: {}", e),
+        }
+    });
+}
+
+/// Rough token count of `text`, at about four characters per token. Good enough
+/// for warnings, not for billing.
+fn estimate_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(4)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemPromptAnalysis {
+    pub estimated_tokens: u64,
+    // Unknown until the model list has been fetched
+    pub context_length: Option<u64>,
+    pub warning: Option<String>,
+}
+
+/// Estimates how much of `model`'s context `system_prompt` takes up and warns
+/// once it passes `system_prompt_warn_fraction`. Only the cached model list is
+/// consulted, so this never waits on the network.
+#[tauri::command]
+fn analyze_system_prompt(
+    capabilities: tauri::State<'_, ModelCapabilities>,
+    system_prompt: String,
+    model: String,
+) -> SystemPromptAnalysis {
+    let estimated_tokens = estimate_tokens(&system_prompt);
+    let model = model.strip_suffix(":online").unwrap_or(&model);
+    let context_length = capabilities
+        .0
+        .lock()
+        .unwrap()
+        .get(model)
+        .and_then(|meta| meta.context_length);
+
+    let fraction = load_settings()
+        .map(|s| s.system_prompt_warn_fraction)
+        .unwrap_or_else(|_| default_system_prompt_warn_fraction());
+    let warning = context_length
+        .filter(|&context| estimated_tokens as f64 > context as f64 * fraction)
+        .map(|context| {
+            format!(
+                "The system prompt takes about {}% of {}'s {} token context",
+                estimated_tokens * 100 / context.max(1),
+                model,
+                context
+            )
+        });
+
+    SystemPromptAnalysis {
+        estimated_tokens,
+        context_length,
+        warning,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletion {
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatChoice {
+    message: ChatChoiceMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatChoiceMessage {
+    content: Option<String>,
+    // Web search citations, handed to the chat window as they are
+    annotations: Option<Vec<serde_json::Value>>,
+}
+
+/// The reply to a chat request.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatReply {
+    pub content: String,
+    pub annotations: Vec<serde_json::Value>,
+    // Differs from the requested model after a fallback
+    pub model: String,
+}
+
+// Overloaded, rate-limited or unreachable providers are worth trying another model for
+fn is_retriable_provider_error(e: &reqwest::Error) -> bool {
+    e.is_timeout()
+        || e.is_connect()
+        || e.status().is_some_and(|status| matches!(status.as_u16(), 429 | 502 | 503 | 504))
+}
+
+/// Trims message text, drops messages left empty or repeating the previous
+/// one (e.g. a double-submitted prompt) and merges other consecutive
+/// messages from the same role. Content that isn't plain text (e.g. image
+/// parts) is passed through and never merged. `send_chat` applies this to
+/// every request; the command lets the frontend preview the result.
+#[tauri::command]
+fn normalize_conversation(messages: Vec<serde_json::Value>) -> Vec<serde_json::Value> {
+    let mut normalized: Vec<serde_json::Value> = Vec::with_capacity(messages.len());
+    for mut message in messages {
+        let Some(text) = message["content"].as_str().map(str::trim) else {
+            normalized.push(message);
+            continue;
+        };
+        if text.is_empty() {
+            continue;
+        }
+        let text = text.to_string();
+
+        if let Some(previous) = normalized.last_mut() {
+            if previous["role"] == message["role"] {
+                if let Some(previous_text) = previous["content"].as_str() {
+                    // The previous message may already hold merged ones, so compare the last
+                    if previous_text == text || previous_text.ends_with(&format!("\n\n{}", text)) {
+                        continue;
+                    }
+                    previous["content"] = serde_json::json!(format!("{}\n\n{}", previous_text, text));
+                    continue;
+                }
+            }
+        }
+        message["content"] = serde_json::json!(text);
+        normalized.push(message);
+    }
+    normalized
+}
+
+/// Sampling parameters for a single request, never saved. Unset fields are
+/// left out of the request so the model's own defaults apply.
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestOverrides {
+    pub temperature: Option<f64>,
+    pub max_tokens: Option<u32>,
+    pub top_p: Option<f64>,
+    // Replaces `stop_sequences` for this request; an empty list sends none
+    pub stop: Option<Vec<String>>,
+}
+
+impl RequestOverrides {
+    fn validate(&self) -> Result<(), AppError> {
+        if let Some(temperature) = self.temperature.filter(|t| !(0.0..=2.0).contains(t)) {
+            return Err(AppError::Config(format!("Temperature must be between 0 and 2, got {}", temperature)));
+        }
+        if let Some(top_p) = self.top_p.filter(|p| !(*p > 0.0 && *p <= 1.0)) {
+            return Err(AppError::Config(format!("top_p must be above 0 and at most 1, got {}", top_p)));
+        }
+        if self.max_tokens == Some(0) {
+            return Err(AppError::Config("max_tokens must be at least 1".to_string()));
+        }
+        if let Some(stop) = &self.stop {
+            normalize_stop_sequences(stop)?;
+        }
+        Ok(())
+    }
+
+    fn apply(&self, body: &mut serde_json::Value) {
+        if let Some(temperature) = self.temperature {
+            body["temperature"] = serde_json::json!(temperature);
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            body["max_tokens"] = serde_json::json!(max_tokens);
+        }
+        if let Some(top_p) = self.top_p {
+            body["top_p"] = serde_json::json!(top_p);
+        }
+    }
+}
+
+/// Lets the chat window check its one-off sampling parameters before sending.
+#[tauri::command]
+fn validate_request_overrides(overrides: RequestOverrides) -> Result<(), AppError> {
+    recorded(|| {
+        overrides.validate()
+    })
+}
+
+// Search results OpenRouter's web plugin adds to a "/s" request
+const WEB_SEARCH_MAX_RESULTS: u32 = 5;
+
+/// Sends a single non-streaming chat completion and returns the reply.
+/// `overrides` sets the sampling parameters for this request only, and
+/// `web_search` turns on OpenRouter's web plugin. If the selected model's
+/// provider is unavailable, each of `fallback_models` is tried in turn and
+/// `model-fell-back` is emitted with the substitute.
+async fn send_chat(
+    app: &tauri::AppHandle,
+    settings: &Settings,
+    messages: Vec<serde_json::Value>,
+    overrides: Option<&RequestOverrides>,
+    web_search: bool,
+) -> Result<ChatReply, AppError> {
+    if settings.api_key.is_empty() {
+        return Err(AppError::Auth("No API key configured".to_string()));
+    }
+    if let Some(overrides) = overrides {
+        overrides.validate()?;
+    }
+
+    let messages = normalize_conversation(messages);
+    let client = app.state::<HttpClient>().0.clone();
+    let capabilities = app.state::<ModelCapabilities>();
+    let stop = normalize_stop_sequences(
+        overrides
+            .and_then(|o| o.stop.as_deref())
+            .unwrap_or(settings.stop_sequences.as_slice()),
+    )?;
+    let models = std::iter::once(&settings.selected_model)
+        .chain(&settings.fallback_models)
+        .map(|model| resolve_model_id(settings, model));
+
+    let mut last_error = None;
+    for (attempt, model) in models.enumerate() {
+        // A suffix on the model id, so only added once aliases are resolved
+        let model = if web_search { format!("{}:online", model) } else { model };
+        if attempt > 0 {
+            let _ = app.emit("model-fell-back", &model);
+        }
+
+        let mut body = serde_json::json!({
+            "model": model,
+            "messages": messages,
+        });
+        if let Some(effort) = settings.reasoning_effort {
+            if supports_reasoning(&client, &capabilities, &settings.api_key, &model).await {
+                body["reasoning"] = serde_json::json!({ "effort": effort });
+            }
+        }
+        if !stop.is_empty() {
+            body["stop"] = serde_json::json!(stop);
+        }
+        body["provider"] = provider_request_json(settings.provider_preferences.as_ref());
+        if web_search {
+            body["plugins"] = serde_json::json!([{ "id": "web", "max_results": WEB_SEARCH_MAX_RESULTS }]);
+        }
+        if let Some(overrides) = overrides {
+            overrides.apply(&mut body);
+        }
+
+        let response = match openrouter_request(&client, reqwest::Method::POST, &settings.api_key, "/chat/completions")
+            .json(&body)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+        {
+            Ok(response) => response,
+            Err(e) if is_retriable_provider_error(&e) => {
+                last_error = Some(AppError::from_http(&format!("Chat request to {} failed", model), e));
+                continue;
+            }
+            Err(e) => return Err(AppError::from_http("Chat request failed", e)),
+        };
+        let completion: ChatCompletion = response
+            .json()
+            .await
+            .map_err(|e| AppError::Network(format!("Failed to parse chat response: {}", e)))?;
+
+        let message = completion
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message)
+            .filter(|message| message.content.is_some())
+            .ok_or_else(|| AppError::Network("Chat response contained no message".to_string()))?;
+        return Ok(ChatReply {
+            content: message.content.unwrap_or_default(),
+            annotations: message.annotations.unwrap_or_default(),
+            model,
+        });
+    }
+
+    Err(last_error.unwrap_or_else(|| AppError::Network("No model to send the chat to".to_string())))
+}
+
+// Silent while do-not-disturb is on, like everything else that would interrupt
+fn notify_response_ready(app: &tauri::AppHandle, body: &str) {
+    if is_do_not_disturb(app) {
+        return;
+    }
+    let _ = app
+        .notification()
+        .builder()
+        .title("AI Quick Access")
+        .body(body)
+        .show();
+}
+
+fn read_system_prompt_file(path: &std::path::Path) -> std::io::Result<String> {
+    let bytes = fs::read(path)?;
+    if bytes.len() <= MAX_SYSTEM_PROMPT_FILE_BYTES {
+        return Ok(String::from_utf8_lossy(&bytes).into_owned());
+    }
+    eprintln!(
+        "System prompt file {} is {} bytes; only the first {} are used",
+        path.display(),
+        bytes.len(),
+        MAX_SYSTEM_PROMPT_FILE_BYTES
+    );
+    Ok(String::from_utf8_lossy(&bytes[..MAX_SYSTEM_PROMPT_FILE_BYTES]).into_owned())
+}
+
+/// The system prompt to send: the session override if there is one, else the
+/// contents of `system_prompt_file` if set and readable, else the inline
+/// `system_prompt`.
+fn effective_system_prompt(app: &tauri::AppHandle, settings: &Settings) -> String {
+    if let Some(prompt) = app.state::<SessionSystemPrompt>().0.lock().unwrap().clone() {
+        return prompt;
+    }
+    let Some(path) = settings.system_prompt_file.as_deref().filter(|p| !p.trim().is_empty()) else {
+        return settings.system_prompt.clone();
+    };
+    let path = PathBuf::from(path.trim());
+    let modified = match fs::metadata(&path).and_then(|m| m.modified()) {
+        Ok(modified) => modified,
+        Err(e) => {
+            eprintln!("System prompt file {} unavailable, using the inline prompt: {}", path.display(), e);
+            return settings.system_prompt.clone();
+        }
+    };
+
+    let cache = app.state::<SystemPromptCache>();
+    let mut cache = cache.0.lock().unwrap();
+    if let Some(cached) = cache.as_ref().filter(|c| c.path == path && c.modified == modified) {
+        return cached.content.clone();
+    }
+    match read_system_prompt_file(&path) {
+        Ok(content) => {
+            *cache = Some(CachedSystemPrompt {
+                path,
+                modified,
+                content: content.clone(),
+            });
+            content
+        }
+        Err(e) => {
+            eprintln!("Failed to read system prompt file {}, using the inline prompt: {}", path.display(), e);
+            settings.system_prompt.clone()
+        }
+    }
+}
+
+/// The system prompt the chat window should send.
+#[tauri::command]
+fn get_effective_system_prompt(app: tauri::AppHandle) -> Result<String, AppError> {
+    recorded(|| {
+        Ok(effective_system_prompt(&app, &load_settings()?))
+    })
+}
+
+/// Drops the cached `system_prompt_file` contents and reads the file again.
+#[tauri::command]
+fn reload_system_prompt(app: tauri::AppHandle) -> Result<String, AppError> {
+    recorded(|| {
+        *app.state::<SystemPromptCache>().0.lock().unwrap() = None;
+        get_effective_system_prompt(app)
+    })
+}
+
+/// Uses `prompt` as the system prompt until the next new chat or restart,
+/// without saving it. `None` goes back to the configured prompt.
+#[tauri::command]
+fn set_session_system_prompt(session: tauri::State<'_, SessionSystemPrompt>, prompt: Option<String>) {
+    *session.0.lock().unwrap() = prompt;
+}
+
+/// Zero-UI flow: answers the clipboard text with the default model and puts the
+/// reply back on the clipboard, reporting the outcome as a notification only.
+async fn quick_reply(app: tauri::AppHandle) -> Result<(), AppError> {
+    let settings = load_settings()?;
+    let text = read_clipboard_text(&app)?;
+    if text.trim().is_empty() {
+        return Err(AppError::InvalidInput("Clipboard is empty".to_string()));
+    }
+
+    let mut messages = Vec::new();
+    let system_prompt = effective_system_prompt(&app, &settings);
+    if !system_prompt.is_empty() {
+        messages.push(serde_json::json!({ "role": "system", "content": system_prompt }));
+    }
+    messages.push(serde_json::json!({
+        "role": "user",
+        "content": format!("{}\n\n{}", settings.quick_reply_prompt, text),
+    }));
+
+    let reply = send_chat(&app, &settings, messages, None, false).await?.content;
+    on_chat_completed(&settings, &text, &reply);
+
+    app.clipboard()
+        .write_text(reply)
+        .map_err(|e| AppError::Internal(format!("Failed to write clipboard: {}", e)))?;
+    notify_response_ready(&app, "Reply copied to clipboard");
+    Ok(())
+}
+
+/// Sends the chat window's conversation. `model` is the selected model or one
+/// picked with a model shortcut such as "/h", and `web_search` is the "/s"
+/// command. The system prompt is added here, and the reply is archived and
+/// piped like every other completed chat.
+#[tauri::command]
+async fn send_chat_message(
+    app: tauri::AppHandle,
+    messages: Vec<serde_json::Value>,
+    model: String,
+    web_search: bool,
+    overrides: Option<RequestOverrides>,
+) -> Result<ChatReply, AppError> {
+    recorded_async(async move {
+        let settings = Settings {
+            selected_model: model,
+            ..load_settings()?
+        };
+        let question = messages
+            .iter()
+            .rev()
+            .find(|message| message["role"] == "user")
+            .and_then(|message| message["content"].as_str())
+            .unwrap_or_default()
+            .to_string();
+
+        let mut conversation = Vec::with_capacity(messages.len() + 1);
+        let system_prompt = effective_system_prompt(&app, &settings);
+        if !system_prompt.is_empty() {
+            conversation.push(serde_json::json!({ "role": "system", "content": system_prompt }));
+        }
+        conversation.extend(messages);
+
+        let reply = send_chat(&app, &settings, conversation, overrides.as_ref(), web_search).await?;
+        on_chat_completed(&settings, &question, &reply.content);
+        Ok(reply)
+    })
+    .await
+}
+
+/// Lists the models `api_key` can actually use. If the key's limits can't be
+/// read, every model is returned and paid ones are flagged as needing credits.
+#[tauri::command]
+async fn fetch_available_models(
+    client: tauri::State<'_, HttpClient>,
+    capabilities: tauri::State<'_, ModelCapabilities>,
+    api_key: String,
+) -> Result<Vec<AvailableModel>, AppError> {
+    recorded_async(async move {
+        let models: OpenRouterList<Vec<OpenRouterModel>> = openrouter_get(&client.0, &api_key, "/models").await?;
+        remember_model_capabilities(&capabilities, &models.data);
+        let key_info = openrouter_get::<OpenRouterList<OpenRouterKeyInfo>>(&client.0, &api_key, "/key")
+            .await
+            .map(|info| info.data);
+
+        let mut available: Vec<AvailableModel> = models
+            .data
+            .into_iter()
+            .filter_map(|model| {
+                let paid = !model.pricing.is_free();
+                let requires_credits = match &key_info {
+                    Ok(info) if paid && !info.can_use_paid_models() => return None,
+                    Ok(_) => false,
+                    Err(_) => paid,
+                };
+                Some(AvailableModel {
+                    id: model.id,
+                    name: model.name,
+                    requires_credits,
+                })
+            })
+            .collect();
+
+        available.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(available)
+    })
+    .await
+}
+
+const BENCHMARK_TIMEOUT: Duration = Duration::from_secs(60);
+const DEFAULT_BENCHMARK_PROMPT: &str = "Reply with the single word: ready";
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BenchmarkResult {
+    pub model: String,
+    pub time_to_first_token_ms: Option<u64>,
+    pub total_ms: u64,
+}
+
+// Whether a server-sent event line carries generated text. Keep-alive comments
+// and the role-only first delta don't count.
+fn sse_line_has_content(line: &[u8]) -> bool {
+    std::str::from_utf8(line)
+        .ok()
+        .and_then(|line| line.trim().strip_prefix("data:"))
+        .map(str::trim)
+        .filter(|data| *data != "[DONE]")
+        .and_then(|data| serde_json::from_str::<serde_json::Value>(data).ok())
+        .and_then(|event| event["choices"][0]["delta"]["content"].as_str().map(|c| !c.is_empty()))
+        .unwrap_or(false)
+}
+
+/// Streams one short completion from `model` and times it. An empty `prompt`
+/// falls back to a fixed one so runs are comparable across models.
+#[tauri::command]
+async fn benchmark_model(
+    client: tauri::State<'_, HttpClient>,
+    model: String,
+    prompt: String,
+) -> Result<BenchmarkResult, AppError> {
+    recorded_async(async move {
+        let settings = load_settings()?;
+        if settings.api_key.is_empty() {
+            return Err(AppError::Auth("No API key configured".to_string()));
+        }
+
+        let prompt = if prompt.trim().is_empty() { DEFAULT_BENCHMARK_PROMPT.to_string() } else { prompt };
+        let body = serde_json::json!({
+            "model": model,
+            "messages": [{ "role": "user", "content": prompt }],
+            "stream": true,
+        });
+
+        let started = Instant::now();
+        let mut response = openrouter_request(&client.0, reqwest::Method::POST, &settings.api_key, "/chat/completions")
+            .json(&body)
+            .timeout(BENCHMARK_TIMEOUT)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AppError::from_http("Benchmark request failed", e))?;
+
+        let mut time_to_first_token = None;
+        let mut pending: Vec<u8> = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| AppError::from_http("Benchmark response failed", e))?
+        {
+            if time_to_first_token.is_some() {
+                continue;
+            }
+            // Events can be split across chunks, so only look at complete lines
+            pending.extend_from_slice(&chunk);
+            while let Some(newline) = pending.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = pending.drain(..=newline).collect();
+                if sse_line_has_content(&line) {
+                    time_to_first_token = Some(started.elapsed());
+                    break;
+                }
+            }
+        }
+
+        Ok(BenchmarkResult {
+            model,
+            time_to_first_token_ms: time_to_first_token.map(|d| d.as_millis() as u64),
+            total_ms: started.elapsed().as_millis() as u64,
+        })
+    })
+    .await
+}
+
+// Enough to compare a handful of models without tripping provider rate limits
+const FAN_OUT_CONCURRENCY: usize = 3;
+
+/// Set by `cancel_fan_out` to stop a running `fan_out_chat` from starting any
+/// more requests. Ones already in flight still finish.
+#[derive(Default)]
+struct FanOutCancel(AtomicBool);
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FanOutResult {
+    pub model: String,
+    pub response: Option<String>,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+/// Sends `prompt` to every model in `models`, at most `FAN_OUT_CONCURRENCY` at
+/// a time, without falling back to other models. Each result is emitted as
+/// `fan-out-result` when it arrives; all of them are returned in `models` order.
+/// `overrides` applies to every request.
+#[tauri::command]
+async fn fan_out_chat(
+    app: tauri::AppHandle,
+    prompt: String,
+    models: Vec<String>,
+    overrides: Option<RequestOverrides>,
+) -> Result<Vec<FanOutResult>, AppError> {
+    recorded_async(async move {
+        let settings = load_settings()?;
+        if settings.api_key.is_empty() {
+            return Err(AppError::Auth("No API key configured".to_string()));
+        }
+        if prompt.trim().is_empty() {
+            return Err(AppError::InvalidInput("Prompt is empty".to_string()));
+        }
+        // Checked up front so a bad value fails once, not once per model
+        if let Some(overrides) = &overrides {
+            overrides.validate()?;
+        }
+
+        let mut messages = Vec::new();
+        let system_prompt = effective_system_prompt(&app, &settings);
+        if !system_prompt.is_empty() {
+            messages.push(serde_json::json!({ "role": "system", "content": system_prompt }));
+        }
+        messages.push(serde_json::json!({ "role": "user", "content": prompt }));
+
+        app.state::<FanOutCancel>().0.store(false, Ordering::SeqCst);
+        let queue = std::sync::Arc::new(Mutex::new(
+            models.iter().cloned().enumerate().collect::<std::collections::VecDeque<_>>(),
+        ));
+
+        let workers: Vec<_> = (0..FAN_OUT_CONCURRENCY.min(models.len()))
+            .map(|_| {
+                let app = app.clone();
+                let settings = settings.clone();
+                let messages = messages.clone();
+                let queue = queue.clone();
+                let overrides = overrides.clone();
+                tauri::async_runtime::spawn(async move {
+                    let mut results = Vec::new();
+                    loop {
+                        if app.state::<FanOutCancel>().0.load(Ordering::SeqCst) {
+                            break;
+                        }
+                        let Some((index, model)) = queue.lock().ok().and_then(|mut q| q.pop_front()) else {
+                            break;
+                        };
+
+                        let settings = Settings {
+                            selected_model: model.clone(),
+                            fallback_models: Vec::new(),
+                            ..settings.clone()
+                        };
+                        let started = Instant::now();
+                        let reply = send_chat(&app, &settings, messages.clone(), overrides.as_ref(), false).await;
+                        let result = FanOutResult {
+                            model,
+                            latency_ms: started.elapsed().as_millis() as u64,
+                            error: reply.as_ref().err().map(|e| e.to_string()),
+                            response: reply.ok().map(|reply| reply.content),
+                        };
+                        let _ = app.emit("fan-out-result", &result);
+                        results.push((index, result));
+                    }
+                    results
+                })
+            })
+            .collect();
+
+        let mut results = Vec::new();
+        for worker in workers {
+            results.extend(
+                worker
+                    .await
+                    .map_err(|e| AppError::Internal(format!("Fan-out worker failed: {}", e)))?,
+            );
+        }
+        results.sort_by_key(|(index, _)| *index);
+        Ok(results.into_iter().map(|(_, result)| result).collect())
+    })
+    .await
+}
+
+/// Stops a running `fan_out_chat` after its in-flight requests.
+#[tauri::command]
+fn cancel_fan_out(cancel: tauri::State<'_, FanOutCancel>) {
+    cancel.0.store(true, Ordering::SeqCst);
+}
+
+const EXTERNAL_URL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+const PREVIEW_URL_SCHEMES: &[&str] = &["http", "https"];
+
+fn parse_link_url(url: &str, allowed_schemes: &[&str]) -> Result<tauri::Url, AppError> {
+    let parsed = tauri::Url::parse(url.trim()).map_err(|e| AppError::InvalidInput(format!("Invalid URL '{}': {}", url, e)))?;
+
+    if !allowed_schemes.contains(&parsed.scheme()) {
+        return Err(AppError::InvalidInput(format!("Refusing to open '{}' URL", parsed.scheme())));
+    }
+    Ok(parsed)
+}
+
+/// Opens a link from an AI response in the default browser instead of letting
+/// the webview navigate away. Anything but web and mail links is refused.
+#[tauri::command]
+fn open_external_url(app: tauri::AppHandle, url: String) -> Result<(), AppError> {
+    recorded(|| {
+        let parsed = parse_link_url(&url, EXTERNAL_URL_SCHEMES)?;
+
+        app.opener()
+            .open_url(parsed.as_str(), None::<&str>)
+            .map_err(|e| AppError::Internal(format!("Failed to open URL: {}", e)))
+    })
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyEntry {
+    pub name: String,
+    // Only the first and last few characters, never the whole key
+    pub masked_key: String,
+    pub active: bool,
+}
+
+fn mask_api_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 12 {
+        return "*".repeat(chars.len());
+    }
+    let head: String = chars[..6].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}…{}", head, tail)
+}
+
+#[tauri::command]
+fn list_api_keys() -> Result<Vec<ApiKeyEntry>, AppError> {
+    recorded(|| {
+        let settings = load_settings()?;
+        let mut keys: Vec<ApiKeyEntry> = settings
+            .named_api_keys
+            .iter()
+            .map(|(name, key)| ApiKeyEntry {
+                name: name.clone(),
+                masked_key: mask_api_key(key),
+                active: settings.active_key_name.as_ref() == Some(name),
+            })
+            .collect();
+        keys.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(keys)
+    })
+}
+
+/// Saves `key` under `name`, replacing any key of that name. The first key
+/// added becomes the active one.
+#[tauri::command]
+async fn add_api_key(app: tauri::AppHandle, name: String, key: String) -> Result<(), AppError> {
+    recorded_async(async move {
+        let name = name.trim().to_string();
+        let key = key.trim().to_string();
+        if name.is_empty() || key.is_empty() {
+            return Err(AppError::Config("API key name and value cannot be empty".to_string()));
+        }
+
+        let mut settings = load_settings()?;
+        settings.named_api_keys.insert(name.clone(), key);
+        if settings.active_key_name.is_none() {
+            settings.active_key_name = Some(name);
+        }
+        apply_active_api_key(&app, settings)
+    })
+    .await
+}
+
+/// Forgets the key named `name`. Removing the active key activates the first
+/// remaining one by name, if any.
+#[tauri::command]
+async fn remove_api_key(app: tauri::AppHandle, name: String) -> Result<(), AppError> {
+    recorded_async(async move {
+        let mut settings = load_settings()?;
+        if settings.named_api_keys.remove(&name).is_none() {
+            return Err(AppError::NotFound(format!("No API key named '{}'", name)));
+        }
+        if settings.active_key_name.as_ref() == Some(&name) {
+            settings.active_key_name = settings.named_api_keys.keys().min().cloned();
+        }
+        apply_active_api_key(&app, settings)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn set_active_api_key(app: tauri::AppHandle, name: String) -> Result<(), AppError> {
+    recorded_async(async move {
+        let mut settings = load_settings()?;
+        if !settings.named_api_keys.contains_key(&name) {
+            return Err(AppError::NotFound(format!("No API key named '{}'", name)));
+        }
+        settings.active_key_name = Some(name);
+        apply_active_api_key(&app, settings)
+    })
+    .await
+}
+
+/// Points `api_key` at the active named key, then saves and broadcasts the settings.
+fn apply_active_api_key(app: &tauri::AppHandle, mut settings: Settings) -> Result<(), AppError> {
+    settings.api_key = settings
+        .active_key_name
+        .as_ref()
+        .and_then(|name| settings.named_api_keys.get(name))
+        .cloned()
+        .unwrap_or_default();
+    write_settings(&settings)?;
+    app.emit("settings-changed", &settings).map_err(AppError::from)
+}
+
+/// Signs out: forgets every API key, including named ones, and drops anything
+/// cached for them. Fails if `configs.json` can't be rewritten, since it would
+/// still hold the old key.
+#[tauri::command]
+async fn clear_api_key(app: tauri::AppHandle, credits: tauri::State<'_, CreditsCache>) -> Result<(), AppError> {
+    recorded_async(async move {
+        let mut settings = load_settings()?;
+        settings.api_key.clear();
+        settings.named_api_keys.clear();
+        settings.active_key_name = None;
+
+        if let Some(fallback) = write_settings_with_fallback(&settings)? {
+            return Err(AppError::Config(format!(
+                "Saved settings without the API key to {}, but configs.json is locked and still contains it",
+                fallback.display()
+            )));
+        }
+        *credits.0.lock().unwrap() = None;
+        app.emit("settings-changed", &settings).map_err(AppError::from)
+    })
+    .await
+}
+
+fn redact_secret(secret: &str) -> String {
+    if secret.is_empty() {
+        String::new()
+    } else {
+        "<redacted>".to_string()
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingChange {
+    pub field: String,
+    pub current: serde_json::Value,
+    pub default: serde_json::Value,
+}
+
+/// Every setting that differs from `default_settings()`, by its camelCase
+/// name as in `configs.json`. API keys are redacted.
+#[tauri::command]
+fn settings_diff() -> Result<Vec<SettingChange>, AppError> {
+    recorded(|| {
+        let mut settings = load_settings()?;
+        settings.api_key = redact_secret(&settings.api_key);
+        for key in settings.named_api_keys.values_mut() {
+            *key = redact_secret(key);
+        }
+
+        let to_fields = |settings: &Settings| match serde_json::to_value(settings) {
+            Ok(serde_json::Value::Object(fields)) => Ok(fields),
+            Ok(_) => Err(AppError::Internal("Settings did not serialize to an object".to_string())),
+            Err(e) => Err(AppError::Internal(format!("Failed to serialize settings: {}", e))),
+        };
+        let current = to_fields(&settings)?;
+        let mut defaults = to_fields(&default_settings())?;
+
+        // `Map` keeps keys sorted, so the changes come out in a stable order
+        Ok(current
+            .into_iter()
+            .filter_map(|(field, current)| {
+                let default = defaults.remove(&field).unwrap_or(serde_json::Value::Null);
+                (current != default).then_some(SettingChange { field, current, default })
+            })
+            .collect())
+    })
+}
+
+/// Writes a human-readable JSON snapshot of the app's runtime state to `path`
+/// for attaching to bug reports. The API key is never included.
+#[tauri::command]
+fn export_diagnostics(app: tauri::AppHandle, path: String) -> Result<(), AppError> {
+    recorded(|| {
+        let mut settings = load_settings()?;
+        settings.api_key = redact_secret(&settings.api_key);
+        for key in settings.named_api_keys.values_mut() {
+            *key = redact_secret(key);
+        }
+
+        let defaults = default_shortcuts();
+        let shortcuts: serde_json::Map<String, serde_json::Value> = [
+            ("focus", defaults.focus),
+            ("newChat", defaults.new_chat),
+            ("scratch", defaults.scratch),
+            ("appendToChat", defaults.append_to_chat),
+            ("quickReply", defaults.quick_reply),
+            ("clickThrough", defaults.click_through),
+            ("abToggle", ab_toggle_shortcut(&settings)),
+        ]
+        .into_iter()
+        .map(|(name, shortcut)| {
+            let status = serde_json::json!({
+                "accelerator": shortcut_to_string(&shortcut),
+                "registered": app.global_shortcut().is_registered(shortcut),
+            });
+            (name.to_string(), status)
+        })
+        .collect();
+
+        let monitors: Vec<serde_json::Value> = app
+            .available_monitors()
+            .unwrap_or_default()
+            .iter()
+            .map(|monitor| {
+                serde_json::json!({
+                    "name": monitor.name(),
+                    "position": [monitor.position().x, monitor.position().y],
+                    "size": [monitor.size().width, monitor.size().height],
+                    "scaleFactor": monitor.scale_factor(),
+                })
+            })
+            .collect();
+
+        let diagnostics = serde_json::json!({
+            "appVersion": app.package_info().version.to_string(),
+            "os": std::env::consts::OS,
+            "arch": std::env::consts::ARCH,
+            "settings": settings,
+            "shortcuts": shortcuts,
+            "monitors": monitors,
+            // The app logs to stderr only, so the recent errors stand in for a log tail
+            "recentErrors": get_recent_errors(),
+        });
+
+        let contents = serde_json::to_string_pretty(&diagnostics)
+            .map_err(|e| AppError::Config(format!("Failed to serialize diagnostics: {}", e)))?;
+        fs::write(&path, contents).map_err(|e| AppError::Config(format!("Failed to write diagnostics file: {}", e)))
+    })
+}
+
+/// Shows a web page in a separate, decorated in-app window. The window is reused
+/// for later previews. It isn't listed in any capability, so pages loaded there
+/// get no access to the app's commands.
+#[tauri::command]
+async fn open_preview_window(app: tauri::AppHandle, url: String) -> Result<(), AppError> {
+    recorded_async(async move {
+        let parsed = parse_link_url(&url, PREVIEW_URL_SCHEMES)?;
+
+        if let Some(window) = app.get_webview_window("preview") {
+            window.navigate(parsed)?;
+            window.show()?;
+            window.set_focus()?;
+            return Ok(());
+        }
+
+        WebviewWindowBuilder::new(&app, "preview", WebviewUrl::External(parsed))
+            .title("Preview")
+            .inner_size(900.0, 700.0)
+            .decorations(true)
+            .build()
+            .map_err(|e| AppError::Window(format!("Failed to open preview window: {}", e)))?;
+        Ok(())
+    })
+    .await
+}
+
+// Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
+#[tauri::command]
+fn greet(name: &str) -> String {
+    format!("Hello, {}! You've been greeted from Rust!", name)
+}
+
+#[tauri::command]
+async fn open_settings(app: tauri::AppHandle) -> Result<(), AppError> {
+    recorded_async(async move {
+        if let Some(window) = app.get_webview_window("settings") {
+            window.show()?;
+            window.set_focus()?;
+        }
+        Ok(())
+    })
+    .await
+}
+
+fn get_scratch_path(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::Config(format!("Could not find app data directory: {}", e)))?;
+    Ok(data_dir.join("scratch.txt"))
+}
+
+#[tauri::command]
+fn load_scratch(app: tauri::AppHandle) -> Result<String, AppError> {
+    recorded(|| {
+        let scratch_path = get_scratch_path(&app)?;
+
+        if !scratch_path.exists() {
+            return Ok(String::new());
+        }
+
+        fs::read_to_string(&scratch_path).map_err(|e| AppError::Config(format!("Failed to read scratch file: {}", e)))
+    })
+}
+
+#[tauri::command]
+fn save_scratch(app: tauri::AppHandle, content: String) -> Result<(), AppError> {
+    recorded(|| {
+        let scratch_path = get_scratch_path(&app)?;
+        if let Some(parent) = scratch_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| AppError::Config(format!("Failed to create app data directory: {}", e)))?;
+        }
+
+        fs::write(&scratch_path, content).map_err(|e| AppError::Config(format!("Failed to write scratch file: {}", e)))
+    })
+}
+
+const MAX_RECENT_PROMPTS: usize = 50;
+
+fn get_recent_prompts_path(app: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::Config(format!("Could not find app data directory: {}", e)))?;
+    Ok(data_dir.join("recent_prompts.json"))
+}
+
+fn write_recent_prompts(app: &tauri::AppHandle, prompts: &[String]) -> Result<(), AppError> {
+    let prompts_path = get_recent_prompts_path(app)?;
+    if let Some(parent) = prompts_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| AppError::Config(format!("Failed to create app data directory: {}", e)))?;
+    }
+
+    let storage_format = load_settings().map(|s| s.storage_format).unwrap_or_default();
+    let contents = storage_format
+        .serialize(prompts)
+        .map_err(|e| AppError::Config(format!("Failed to serialize recent prompts: {}", e)))?;
+    fs::write(&prompts_path, contents).map_err(|e| AppError::Config(format!("Failed to write recent prompts file: {}", e)))
+}
+
+/// Rewrites the settings and recent prompts files in the current
+/// `storage_format`, e.g. after switching it.
+#[tauri::command]
+async fn recompress_storage(app: tauri::AppHandle) -> Result<(), AppError> {
+    recorded_async(async move {
+        write_settings(&load_settings()?)?;
+        write_recent_prompts(&app, &get_recent_prompts(app.clone())?)
+    })
+    .await
+}
+
+/// Returns the most recent prompts, newest first.
+#[tauri::command]
+fn get_recent_prompts(app: tauri::AppHandle) -> Result<Vec<String>, AppError> {
+    recorded(|| {
+        let prompts_path = get_recent_prompts_path(&app)?;
+
+        if !prompts_path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let contents = fs::read_to_string(&prompts_path)
+            .map_err(|e| AppError::Config(format!("Failed to read recent prompts file: {}", e)))?;
+        serde_json::from_str(&contents).map_err(|e| AppError::Config(format!("Failed to parse recent prompts file: {}", e)))
+    })
+}
+
+#[tauri::command]
+fn add_recent_prompt(app: tauri::AppHandle, prompt: String) -> Result<Vec<String>, AppError> {
+    recorded(|| {
+        let mut prompts = get_recent_prompts(app.clone())?;
+        let prompt = prompt.trim();
+
+        // Re-sending the same prompt shouldn't push everything else out
+        if prompt.is_empty() || prompts.first().map(String::as_str) == Some(prompt) {
+            return Ok(prompts);
+        }
+
+        prompts.insert(0, prompt.to_string());
+        prompts.truncate(MAX_RECENT_PROMPTS);
+        write_recent_prompts(&app, &prompts)?;
+        Ok(prompts)
+    })
+}
+
+#[tauri::command]
+fn clear_recent_prompts(app: tauri::AppHandle) -> Result<(), AppError> {
+    recorded(|| {
+        write_recent_prompts(&app, &[])
+    })
+}
+
+// Past this size the archive is moved aside to `<file>.1` and started afresh
+const ARCHIVE_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// `time` as an ISO 8601 UTC timestamp, e.g. `2024-05-01T09:30:00Z`.
+fn format_utc_timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3_600,
+        secs_of_day % 3_600 / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Appends a timestamped Q/A entry to `archive_to_file`, if set. Failures are
+/// logged; archiving never gets in the way of the chat itself.
+fn append_to_archive(settings: &Settings, question: &str, answer: &str) {
+    use std::io::Write;
+
+    let Some(path) = settings.archive_to_file.as_deref().map(str::trim).filter(|p| !p.is_empty()) else {
+        return;
+    };
+    let path = PathBuf::from(path);
+
+    if fs::metadata(&path).is_ok_and(|m| m.len() > ARCHIVE_MAX_BYTES) {
+        let mut rotated = path.clone().into_os_string();
+        rotated.push(".1");
+        if let Err(e) = fs::rename(&path, &rotated) {
+            eprintln!("Failed to rotate chat archive {}: {}", path.display(), e);
+        }
+    }
+
+    let entry = format!(
+        "[{}]\nQ: {}\nA: {}\n\n",
+        format_utc_timestamp(SystemTime::now()),
+        question.trim(),
+        answer.trim()
+    );
+    let result = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::OpenOptions::new().create(true).append(true).open(&path))
+        .and_then(|mut file| file.write_all(entry.as_bytes()));
+    if let Err(e) = result {
+        eprintln!("Failed to append to chat archive {}: {}", path.display(), e);
+    }
+}
+
+/// Splits `command` into a program and its arguments on whitespace, keeping
+/// single- or double-quoted parts together. There is no other shell syntax:
+/// pipes, redirects and variables are passed through literally.
+fn split_command_line(command: &str) -> Result<Vec<String>, AppError> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut in_part = false;
+    let mut quote = None;
+
+    for c in command.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_part = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_part {
+                    parts.push(std::mem::take(&mut current));
+                    in_part = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_part = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err(AppError::Config(format!("Unterminated quote in command '{}'", command)));
+    }
+    if in_part {
+        parts.push(current);
+    }
+    if parts.is_empty() {
+        return Err(AppError::Config("Command cannot be empty".to_string()));
+    }
+    Ok(parts)
+}
+
+/// Runs `on_complete_command`, if set, with `answer` on its stdin. The command
+/// runs in the background and only its exit status is logged.
+fn run_on_complete_command(settings: &Settings, answer: &str) {
+    use std::io::Write;
+
+    let Some(command) = settings.on_complete_command.as_deref() else {
+        return;
+    };
+    let parts = match split_command_line(command) {
+        Ok(parts) => parts,
+        Err(e) => {
+            eprintln!("Not running on-complete command: {}", e);
+            return;
+        }
+    };
+
+    let answer = answer.to_string();
+    std::thread::spawn(move || {
+        let child = std::process::Command::new(&parts[0])
+            .args(&parts[1..])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("Failed to start on-complete command '{}': {}", parts[0], e);
+                return;
+            }
+        };
+
+        // Dropping stdin closes it, so the command sees the end of the reply
+        if let Some(mut stdin) = child.stdin.take() {
+            if let Err(e) = stdin.write_all(answer.as_bytes()) {
+                eprintln!("Failed to pipe reply to on-complete command: {}", e);
+            }
+        }
+        match child.wait() {
+            Ok(status) => eprintln!("On-complete command '{}' exited with {}", parts[0], status),
+            Err(e) => eprintln!("Failed to wait for on-complete command '{}': {}", parts[0], e),
+        }
+    });
+}
+
+/// Everything that happens once a reply has fully arrived.
+fn on_chat_completed(settings: &Settings, question: &str, answer: &str) {
+    append_to_archive(settings, question, answer);
+    run_on_complete_command(settings, answer);
+}
+
+fn toggle_scratch(app: &tauri::AppHandle) -> Result<(), AppError> {
+    let window = app
+        .get_webview_window("scratch")
+        .ok_or_else(|| AppError::NotFound("Scratch window not found".to_string()))?;
+
+    if window.is_visible().unwrap_or(false) && window.is_focused().unwrap_or(false) {
+        window.hide()?;
+    } else {
+        window.show()?;
+        window.set_focus()?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn toggle_scratch_window(app: tauri::AppHandle) -> Result<(), AppError> {
+    recorded_async(async move {
+        toggle_scratch(&app)
+    })
+    .await
+}
+
+/// Exits right away, or, with `confirm_on_quit` on, brings up the main window
+/// and asks the frontend to confirm. The frontend calls `quit_confirmed` to exit.
+fn request_quit(app: &tauri::AppHandle) {
+    let confirm = load_settings().map(|s| s.confirm_on_quit).unwrap_or(false);
+
+    if confirm {
+        create_or_focus_main_window(app, false);
+        let _ = app.emit("confirm-quit", ());
+    } else {
+        app.exit(0);
+    }
+}
+
+#[tauri::command]
+async fn quit_app(app: tauri::AppHandle) {
+    request_quit(&app);
+}
+
+#[tauri::command]
+async fn quit_confirmed(app: tauri::AppHandle) {
+    app.exit(0);
+}
+
+const RESIZE_FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+fn next_resize_generation(app: &tauri::AppHandle) -> u64 {
+    app.state::<WindowState>()
+        .resize_generation
+        .fetch_add(1, Ordering::SeqCst)
+        + 1
+}
+
+fn set_window_height_anchored(
+    window: &tauri::WebviewWindow,
+    monitor: &tauri::Monitor,
+    anchor: WindowAnchor,
+    new_height: u32,
+) -> Result<(), AppError> {
+    let current_size = window.inner_size()?;
+    let new_size = tauri::PhysicalSize {
+        width: current_size.width,
+        height: new_height,
+    };
+
+    // Resize
+    window.set_size(tauri::Size::Physical(new_size))?;
+
+    // Keep it anchored
+    let position = anchored_position(monitor, anchor, new_size);
+    window.set_position(tauri::Position::Physical(position)).map_err(AppError::from)
+}
+
+/// Resizes the main window to `height_percentage` of its monitor, clamped to
+/// `max_height_percentage`, and returns the percentage actually applied.
+#[tauri::command]
+async fn resize_window(app: tauri::AppHandle, height_percentage: f64) -> Result<f64, AppError> {
+    recorded_async(async move {
+        next_resize_generation(&app);
+        if let Some(window) = app.get_webview_window("main") {
+            if let Ok(Some(monitor)) = window.current_monitor() {
+                let height_percentage = clamp_height_percentage(&monitor, height_percentage);
+                let new_height = (monitor.size().height as f64 * height_percentage).round() as u32;
+                set_window_height_anchored(&window, &monitor, window_anchor(), new_height)?;
+                return Ok(height_percentage);
+            }
+        }
+        Ok(height_percentage)
+    })
+    .await
+}
+
+/// Keeps `height_percentage` between the input row's height and
+/// `max_height_percentage` of `monitor`.
+fn clamp_height_percentage(monitor: &tauri::Monitor, height_percentage: f64) -> f64 {
+    let max = load_settings()
+        .map(|s| s.max_height_percentage)
+        .unwrap_or_else(|_| default_max_height_percentage());
+    let input_row_height = default_window_height() * monitor.scale_factor();
+    let min = (input_row_height / monitor.size().height as f64).min(max);
+    if height_percentage.is_nan() {
+        return max;
+    }
+    height_percentage.clamp(min, max)
+}
+
+/// Like `resize_window`, but grows (or shrinks) the window over `duration_ms`,
+/// re-anchoring on every frame. Gives up as soon as any other resize starts.
+#[tauri::command]
+async fn resize_window_animated(
+    app: tauri::AppHandle,
+    height_percentage: f64,
+    duration_ms: u64,
+) -> Result<f64, AppError> {
+    recorded_async(async move {
+        let generation = next_resize_generation(&app);
+        let window = match app.get_webview_window("main") {
+            Some(window) => window,
+            None => return Ok(height_percentage),
+        };
+        let monitor = match window.current_monitor() {
+            Ok(Some(monitor)) => monitor,
+            _ => return Ok(height_percentage),
+        };
+
+        let height_percentage = clamp_height_percentage(&monitor, height_percentage);
+        let anchor = window_anchor();
+        let start_height = window.inner_size()?.height as f64;
+        let target_height = (monitor.size().height as f64 * height_percentage).round();
+        let frames = (duration_ms / RESIZE_FRAME_INTERVAL.as_millis() as u64).max(1);
+
+        for frame in 1..=frames {
+            if app.state::<WindowState>().resize_generation.load(Ordering::SeqCst) != generation {
+                return Ok(height_percentage);
+            }
+
+            // Ease out so the growth slows down as it settles
+            let progress = frame as f64 / frames as f64;
+            let eased = 1.0 - (1.0 - progress).powi(3);
+            let height = start_height + (target_height - start_height) * eased;
+            set_window_height_anchored(&window, &monitor, anchor, height.round() as u32)?;
+
+            if frame < frames {
+                tokio::time::sleep(RESIZE_FRAME_INTERVAL).await;
+            }
+        }
+        Ok(height_percentage)
+    })
+    .await
+}
+
+const CONVERSATION_HEIGHT_PERCENTAGE: f64 = 0.7;
+const LAYOUT_RESIZE_DURATION_MS: u64 = 200;
+
+/// Grows the main window to show the conversation, or shrinks it back to the
+/// input row, and emits `layout-mode-changed`.
+#[tauri::command]
+async fn set_layout_mode(app: tauri::AppHandle, mode: LayoutMode) -> Result<(), AppError> {
+    recorded_async(async move {
+        *app.state::<WindowState>().layout_mode.lock().unwrap() = mode;
+        match mode {
+            LayoutMode::Conversation => {
+                resize_window_animated(app.clone(), CONVERSATION_HEIGHT_PERCENTAGE, LAYOUT_RESIZE_DURATION_MS).await?;
+            }
+            LayoutMode::Input => reset_window(app.clone()).await?,
+        }
+        app.emit("layout-mode-changed", mode).map_err(AppError::from)
+    })
+    .await
+}
+
+#[tauri::command]
+fn get_layout_mode(window_state: tauri::State<'_, WindowState>) -> LayoutMode {
+    *window_state.layout_mode.lock().unwrap()
+}
+
+#[tauri::command]
+async fn reset_window(app: tauri::AppHandle) -> Result<(), AppError> {
+    recorded_async(async move {
+        next_resize_generation(&app);
+        if let Some(window) = app.get_webview_window("main") {
+            // Set size to 800x150 (800x100 in compact mode)
+            let height = default_window_height();
+            window.set_size(tauri::Size::Logical(tauri::LogicalSize {
+                width: 800.0,
+                height,
+            }))?;
+
+            // Re-anchor
+            if let Ok(Some(monitor)) = window.current_monitor() {
+                 let scale_factor = monitor.scale_factor();
+                 let size_physical = tauri::PhysicalSize {
+                     width: (800.0 * scale_factor) as u32,
+                     height: (height * scale_factor) as u32,
+                 };
+                 let position = anchored_position(&monitor, window_anchor(), size_physical);
+
+                 window.set_position(tauri::Position::Physical(position))?;
+            }
+        }
+        Ok(())
+    })
+    .await
+}
+
+fn get_mouse_position() -> Option<(i32, i32)> {
+    match Mouse::get_mouse_position() {
+        Mouse::Position { x, y } => Some((x, y)),
+        Mouse::Error => None,
+    }
+}
+
+// Gap kept between an anchored window and the edges of the work area, in logical pixels
+const WINDOW_ANCHOR_MARGIN: f64 = 16.0;
+
+fn window_anchor() -> WindowAnchor {
+    load_settings().map(|s| s.window_anchor).unwrap_or_default()
+}
+
+fn anchored_position(
+    monitor: &tauri::Monitor,
+    anchor: WindowAnchor,
+    window_size: tauri::PhysicalSize<u32>,
+) -> PhysicalPosition<i32> {
+    // Centered on the whole monitor, as the window always has been. Corners and
+    // edges use the work area so they stay clear of taskbars and docks.
+    let (area_pos, area_size, margin) = if anchor == WindowAnchor::Center {
+        (*monitor.position(), *monitor.size(), 0)
+    } else {
+        let area = monitor.work_area();
+        let margin = (WINDOW_ANCHOR_MARGIN * monitor.scale_factor()).round() as i32;
+        (area.position, area.size, margin)
+    };
+    anchored_position_in_area(area_pos, area_size, margin, anchor, window_size)
+}
+
+/// Where a `window_size` window goes at `anchor` within an area, keeping
+/// `margin` from its edges.
+fn anchored_position_in_area(
+    area_pos: PhysicalPosition<i32>,
+    area_size: tauri::PhysicalSize<u32>,
+    margin: i32,
+    anchor: WindowAnchor,
+    window_size: tauri::PhysicalSize<u32>,
+) -> PhysicalPosition<i32> {
+    let width = window_size.width as i32;
+    let height = window_size.height as i32;
+
+    let left = area_pos.x + margin;
+    let right = area_pos.x + area_size.width as i32 - width - margin;
+    let top = area_pos.y + margin;
+    let bottom = area_pos.y + area_size.height as i32 - height - margin;
+    let center_x = area_pos.x + (area_size.width as i32 - width) / 2;
+    let center_y = area_pos.y + (area_size.height as i32 - height) / 2;
+
+    let (x, y) = match anchor {
+        WindowAnchor::Center => (center_x, center_y),
+        WindowAnchor::TopCenter => (center_x, top),
+        WindowAnchor::TopLeft => (left, top),
+        WindowAnchor::TopRight => (right, top),
+        WindowAnchor::BottomLeft => (left, bottom),
+        WindowAnchor::BottomRight => (right, bottom),
+    };
+    PhysicalPosition::new(x, y)
+}
+
+fn position_window_on_monitor(window: &tauri::WebviewWindow, monitor: &tauri::Monitor, anchor: WindowAnchor) {
+    if let Ok(window_size) = window.outer_size() {
+        let _ = window.set_position(anchored_position(monitor, anchor, window_size));
+    }
+}
+
+/// Moves the main window to `anchor` on its current monitor without saving it,
+/// so the settings page can preview anchors as the user picks them.
+#[tauri::command]
+async fn preview_window_anchor(app: tauri::AppHandle, anchor: WindowAnchor) -> Result<(), AppError> {
+    recorded_async(async move {
+        let window = app
+            .get_webview_window("main")
+            .ok_or_else(|| AppError::NotFound("Main window not found".to_string()))?;
+        let monitor = window
+            .current_monitor()?
+            .ok_or_else(|| AppError::NotFound("Main window is not on any monitor".to_string()))?;
+
+        position_window_on_monitor(&window, &monitor, anchor);
+        window.set_skip_taskbar(false)?;
+        window.show().map_err(AppError::from)
+    })
+    .await
+}
+
+fn position_window_on_monitor_with_mouse(app: &tauri::AppHandle, window: &tauri::WebviewWindow, anchor: WindowAnchor) {
+    // Get mouse position
+    let mouse_pos = match get_mouse_position() {
+        Some(pos) => pos,
+        None => return, // Fall back to default behavior if we can't get mouse position
+    };
+
+    // Find the monitor that contains the mouse cursor
+    let monitors = match app.available_monitors() {
+        Ok(m) => m,
+        Err(_) => return,
+    };
+
+    for monitor in monitors {
+        let pos = monitor.position();
+        let size = monitor.size();
+        
+        let monitor_x = pos.x;
+        let monitor_y = pos.y;
+        let monitor_width = size.width as i32;
+        let monitor_height = size.height as i32;
+
+        // Check if mouse is within this monitor
+        if mouse_pos.0 >= monitor_x
+            && mouse_pos.0 < monitor_x + monitor_width
+            && mouse_pos.1 >= monitor_y
+            && mouse_pos.1 < monitor_y + monitor_height
+        {
+            position_window_on_monitor(window, &monitor, anchor);
+            break;
+        }
+    }
+}
+
+/// Keeps the window where it was last shown. If the remembered monitor has been
+/// disconnected since, the window is moved to the primary monitor instead.
+fn restore_window_to_last_monitor(app: &tauri::AppHandle, window: &tauri::WebviewWindow, anchor: WindowAnchor) {
+    let last_monitor = app
+        .state::<WindowState>()
+        .last_monitor
+        .lock()
+        .unwrap()
+        .clone();
+
+    let monitors = match app.available_monitors() {
+        Ok(m) => m,
+        Err(_) => return,
+    };
+
+    let still_connected = last_monitor
+        .as_deref()
+        .is_some_and(|name| monitors.iter().any(|m| m.name().map(String::as_str) == Some(name)));
+
+    if !still_connected {
+        if let Ok(Some(primary)) = app.primary_monitor() {
+            position_window_on_monitor(window, &primary, anchor);
+        }
+    }
+}
+
+/// Returns `position` if a window of `size` placed there would have its center on
+/// one of the connected monitors, otherwise a position centered on the primary
+/// monitor. Guards against monitors that were unplugged while the window was hidden.
+fn clamp_to_visible_monitors(
+    app: &tauri::AppHandle,
+    position: PhysicalPosition<i32>,
+    size: tauri::PhysicalSize<u32>,
+) -> PhysicalPosition<i32> {
+    let center_x = position.x + size.width as i32 / 2;
+    let center_y = position.y + size.height as i32 / 2;
+
+    let monitors = app.available_monitors().unwrap_or_default();
+    let visible = monitors.iter().any(|monitor| {
+        let pos = monitor.position();
+        let monitor_size = monitor.size();
+        center_x >= pos.x
+            && center_x < pos.x + monitor_size.width as i32
+            && center_y >= pos.y
+            && center_y < pos.y + monitor_size.height as i32
+    });
+
+    if visible {
+        return position;
+    }
+
+    match app.primary_monitor() {
+        Ok(Some(primary)) => {
+            let pos = primary.position();
+            let primary_size = primary.size();
+            PhysicalPosition::new(
+                pos.x + (primary_size.width as i32 - size.width as i32) / 2,
+                pos.y + (primary_size.height as i32 - size.height as i32) / 2,
+            )
+        }
+        _ => position,
+    }
+}
+
+fn ensure_window_visible(app: &tauri::AppHandle, window: &tauri::WebviewWindow) {
+    if let (Ok(position), Ok(size)) = (window.outer_position(), window.outer_size()) {
+        let clamped = clamp_to_visible_monitors(app, position, size);
+        if clamped != position {
+            let _ = window.set_position(clamped);
+        }
+    }
+}
+
+fn position_main_window(app: &tauri::AppHandle, window: &tauri::WebviewWindow) {
+    let settings = load_settings().unwrap_or_else(|_| default_settings());
+
+    if settings.follow_mouse_monitor {
+        // Move window to the monitor where the mouse is
+        position_window_on_monitor_with_mouse(app, window, settings.window_anchor);
+    } else {
+        restore_window_to_last_monitor(app, window, settings.window_anchor);
+    }
+
+    ensure_window_visible(app, window);
+}
+
+#[tauri::command]
+async fn move_window_to_monitor(app: tauri::AppHandle, name: String) -> Result<(), AppError> {
+    recorded_async(async move {
+        let window = app
+            .get_webview_window("main")
+            .ok_or_else(|| AppError::NotFound("Main window not found".to_string()))?;
+
+        let monitors = app.available_monitors()?;
+        let monitor = monitors
+            .iter()
+            .find(|m| m.name().map(String::as_str) == Some(name.as_str()))
+            .ok_or_else(|| AppError::NotFound(format!("Monitor '{}' not found", name)))?;
+
+        position_window_on_monitor(&window, monitor, window_anchor());
+        remember_window_monitor(&app, &window);
+        Ok(())
+    })
+    .await
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f64,
+    pub monitor: Option<String>,
+}
+
+// Collapses whitespace (including newlines from multi-line prompts) and caps the
+// length so the title stays readable in taskbars and screen readers.
+fn sanitize_window_title(title: &str) -> String {
+    let title = title
+        .split_whitespace()
+        .map(|word| word.chars().filter(|c| !c.is_control()).collect::<String>())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if title.chars().count() > MAX_WINDOW_TITLE_CHARS {
+        let truncated: String = title.chars().take(MAX_WINDOW_TITLE_CHARS - 1).collect();
+        format!("{}…", truncated.trim_end())
+    } else {
+        title
+    }
+}
+
+/// Sets the main window title, e.g. to the current conversation. An empty
+/// `title` restores the default.
+#[tauri::command]
+fn set_window_title(app: tauri::AppHandle, title: String) -> Result<(), AppError> {
+    recorded(|| {
+        let window = app
+            .get_webview_window("main")
+            .ok_or_else(|| AppError::NotFound("Main window not found".to_string()))?;
+
+        let title = sanitize_window_title(&title);
+        let title = if title.is_empty() { MAIN_WINDOW_TITLE } else { title.as_str() };
+        window.set_title(title).map_err(AppError::from)
+    })
+}
+
+/// Physical position and size of the main window, for layout logic in the frontend.
+#[tauri::command]
+fn get_window_geometry(app: tauri::AppHandle) -> Result<WindowGeometry, AppError> {
+    recorded(|| {
+        let window = app
+            .get_webview_window("main")
+            .ok_or_else(|| AppError::NotFound("Main window not found".to_string()))?;
+        window_geometry(&window.as_ref().window())
+    })
+}
+
+fn window_geometry(window: &tauri::Window) -> Result<WindowGeometry, AppError> {
+    let position = window.outer_position()?;
+    let size = window.outer_size()?;
+    let monitor = window
+        .current_monitor()?
+        .and_then(|m| m.name().cloned());
+
+    Ok(WindowGeometry {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+        scale_factor: window.scale_factor()?,
+        monitor,
+    })
+}
+
+const GEOMETRY_EVENT_DEBOUNCE: Duration = Duration::from_millis(150);
+
+// Emits `window-geometry-changed` once the main window has stopped moving or
+// resizing for `GEOMETRY_EVENT_DEBOUNCE`, rather than for every frame of a drag.
+fn schedule_geometry_event(window: &tauri::Window) {
+    let app = window.app_handle().clone();
+    let generation = app
+        .state::<WindowState>()
+        .geometry_event_generation
+        .fetch_add(1, Ordering::SeqCst)
+        + 1;
+    let window = window.clone();
+
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(GEOMETRY_EVENT_DEBOUNCE).await;
+        if app.state::<WindowState>().geometry_event_generation.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        match window_geometry(&window) {
+            Ok(geometry) => {
+                let _ = app.emit("window-geometry-changed", geometry);
+            }
+            Err(err) => eprintln!("Failed to read window geometry: {}", err),
+        }
+    });
+}
+
+fn remember_window_monitor(app: &tauri::AppHandle, window: &tauri::WebviewWindow) {
+    if let Ok(Some(monitor)) = window.current_monitor() {
+        *app.state::<WindowState>().last_monitor.lock().unwrap() = monitor.name().cloned();
+    }
+}
+
+/// Starts a new chat in the frontend, on `new_chat_model` when one is set.
+fn emit_new_chat(app: &tauri::AppHandle) {
+    let new_chat_model = load_settings()
+        .ok()
+        .and_then(|s| s.new_chat_model.as_deref().map(|model| resolve_model_id(&s, model)));
+
+    match new_chat_model {
+        Some(model) => {
+            let _ = app.emit("new-chat-with-model", model);
+        }
+        None => {
+            let _ = app.emit("new-chat", ());
+        }
+    }
+}
+
+fn record_interaction(app: &tauri::AppHandle) {
+    *app.state::<WindowState>().last_interaction.lock().unwrap() = Some(Instant::now());
+}
+
+/// Lets the frontend count sending a message as activity.
+#[tauri::command]
+fn record_chat_interaction(app: tauri::AppHandle) {
+    record_interaction(&app);
+    // Once the new chat is in use, undoing it would lose that
+    *app.state::<ClearedConversation>().0.lock().unwrap() = None;
+}
+
+/// Called by the chat window with the messages a new chat is about to clear.
+#[tauri::command]
+fn stash_cleared_conversation(cleared: tauri::State<'_, ClearedConversation>, messages: serde_json::Value) {
+    if messages.as_array().is_some_and(|m| !m.is_empty()) {
+        *cleared.0.lock().unwrap() = Some((Instant::now(), messages));
+    }
+}
+
+/// Brings back the conversation the last new chat cleared, via `restore-conversation`.
+#[tauri::command]
+async fn restore_last_cleared(app: tauri::AppHandle) -> Result<(), AppError> {
+    recorded_async(async move {
+        let stashed = app.state::<ClearedConversation>().0.lock().unwrap().take();
+        let messages = match stashed {
+            Some((cleared_at, messages)) if cleared_at.elapsed() < CLEARED_CONVERSATION_TTL => messages,
+            _ => return Err(AppError::NotFound("No recently cleared conversation to restore".to_string())),
+        };
+
+        create_or_focus_main_window(&app, false);
+        app.emit("restore-conversation", messages).map_err(AppError::from)
+    })
+    .await
+}
+
+// Whether the conversation has sat untouched for longer than
+// `new_chat_after_idle_mins`, so summoning should start fresh.
+fn is_conversation_stale(app: &tauri::AppHandle) -> bool {
+    let idle_mins = match load_settings().ok().and_then(|s| s.new_chat_after_idle_mins) {
+        Some(mins) => mins,
+        None => return false,
+    };
+    let last_interaction = *app.state::<WindowState>().last_interaction.lock().unwrap();
+    last_interaction.is_some_and(|at| at.elapsed() >= Duration::from_secs(idle_mins * 60))
+}
+
+fn create_or_focus_main_window(app: &tauri::AppHandle, new_chat: bool) {
+    let new_chat = new_chat || is_conversation_stale(app);
+    // Normally preloaded at startup; only built here with `preload_window` off
+    let window = match app.get_webview_window("main") {
+        Some(window) => window,
+        None => match build_main_window(app, false) {
+            Ok(window) => window,
+            Err(e) => {
+                eprintln!("Failed to create main window: {}", e);
+                return;
+            }
+        },
+    };
+
+    position_main_window(app, &window);
+    // A preloaded window is built off the taskbar so it doesn't appear there before its first show
+    let _ = window.set_skip_taskbar(false);
+    // Show and bring to front
+    let _ = window.show();
+    move_to_current_workspace(&window);
+    let _ = window.set_focus();
+    mark_first_show(app);
+    remember_window_monitor(app, &window);
+    let raise_strategy = load_settings().map(|s| s.raise_strategy).unwrap_or_default();
+    match raise_strategy {
+        RaiseStrategy::AlwaysOnTopFlash => {
+            // Set always on top temporarily to ensure it's above all other windows
+            let _ = window.set_always_on_top(true);
+            // Then disable always on top so it behaves normally after, unless pinned
+            let _ = window.set_always_on_top(is_pinned_on_top(app));
+        }
+        RaiseStrategy::RequestUserAttention => {
+            let _ = window.request_user_attention(Some(tauri::UserAttentionType::Informational));
+        }
+        RaiseStrategy::Focus => {}
+    }
+    if new_chat {
+        emit_new_chat(app);
+    }
+}
+
+// X11 window managers otherwise switch back to the desktop the window was
+// created on. Briefly making it sticky (visible on every desktop) and then
+// unsticking it leaves it on the current one.
+#[cfg(target_os = "linux")]
+fn move_to_current_workspace(window: &tauri::WebviewWindow) {
+    if !load_settings().map(|s| s.summon_on_current_workspace).unwrap_or(true) {
+        return;
+    }
+    if !platform_capabilities().window_positioning {
+        eprintln!("Can't move the window to the current workspace under Wayland");
+        return;
+    }
+    let result = window
+        .set_visible_on_all_workspaces(true)
+        .and_then(|_| window.set_visible_on_all_workspaces(false));
+    if let Err(e) = result {
+        eprintln!("Failed to move window to the current workspace: {}", e);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn move_to_current_workspace(_window: &tauri::WebviewWindow) {}
+
+/// Builds the main window with vibrancy applied. A `preload`ed window starts
+/// hidden, unfocused and out of the taskbar, so it can be built at launch
+/// without getting in the way.
+fn build_main_window(app: &tauri::AppHandle, preload: bool) -> tauri::Result<tauri::WebviewWindow> {
+    let window = WebviewWindowBuilder::new(app, "main", WebviewUrl::App("/".into()))
+        .title(MAIN_WINDOW_TITLE)
+        .inner_size(800.0, default_window_height())
+        .decorations(false)
+        .transparent(true)
+        .visible(!preload)
+        .focused(!preload)
+        .skip_taskbar(preload)
+        .build()?;
+
+    let _ = window.set_ignore_cursor_events(is_click_through(app));
+
+    let settings = load_settings().unwrap_or_else(|_| default_settings());
+    // Unsupported on Linux; a no-op there
+    let _ = window.set_shadow(settings.window_shadow);
+
+    #[cfg(target_os = "macos")]
+    {
+        let result = window_vibrancy::apply_vibrancy(
+            &window,
+            macos_vibrancy_material(&settings.macos_vibrancy_material),
+            None,
+            Some(settings.window_corner_radius.unwrap_or(DEFAULT_WINDOW_CORNER_RADIUS)),
+        );
+        record_effects_support(
+            app,
+            EffectsSupport {
+                vibrancy: result.is_ok(),
+                blur: false,
+                probed: true,
+                error: result.err().map(|e| e.to_string()),
+            },
+        );
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        let [r, g, b, a] = settings.windows_blur_tint;
+        let result = window_vibrancy::apply_blur(&window, Some((r, g, b, a)));
+        record_effects_support(
+            app,
+            EffectsSupport {
+                vibrancy: false,
+                blur: result.is_ok(),
+                probed: true,
+                error: result.err().map(|e| e.to_string()),
+            },
+        );
+    }
+
+    Ok(window)
+}
+
+/// Re-applies the Windows blur with another tint without saving it, so the
+/// settings page can preview tints live. Does nothing on other platforms.
+#[tauri::command]
+fn set_blur_tint(app: tauri::AppHandle, r: u8, g: u8, b: u8, a: u8) -> Result<(), AppError> {
+    recorded(|| {
+        #[cfg(target_os = "windows")]
+        {
+            let window = app
+                .get_webview_window("main")
+                .ok_or_else(|| AppError::NotFound("Main window not found".to_string()))?;
+            window_vibrancy::apply_blur(&window, Some((r, g, b, a)))
+                .map_err(|e| AppError::Window(format!("Failed to apply blur: {}", e)))?;
+        }
+        #[cfg(not(target_os = "windows"))]
+        let _ = (app, r, g, b, a);
+
+        Ok(())
+    })
+}
+
+fn read_clipboard_text(app: &tauri::AppHandle) -> Result<String, AppError> {
+    app.clipboard()
+        .read_text()
+        .map_err(|e| AppError::Internal(format!("Failed to read clipboard: {}", e)))
+}
+
+/// Shows the main window and hands the clipboard text to the current
+/// conversation's input. Unlike `new-chat`, the existing chat is left as is.
+fn append_clipboard_to_chat(app: &tauri::AppHandle) -> Result<(), AppError> {
+    let text = read_clipboard_text(app)?;
+    create_or_focus_main_window(app, false);
+    app.emit("append-to-chat", text).map_err(AppError::from)
+}
+
+fn is_do_not_disturb(app: &tauri::AppHandle) -> bool {
+    app.state::<WindowState>().do_not_disturb.load(Ordering::SeqCst)
+}
+
+fn is_click_through(app: &tauri::AppHandle) -> bool {
+    app.state::<WindowState>().click_through.load(Ordering::SeqCst)
+}
+
+// Both do not disturb and click-through keep the window above other windows
+fn is_pinned_on_top(app: &tauri::AppHandle) -> bool {
+    is_do_not_disturb(app) || is_click_through(app)
+}
+
+/// Runtime-only: do not disturb is off again after a restart.
+#[tauri::command]
+fn set_do_not_disturb(app: tauri::AppHandle, enabled: bool) -> Result<(), AppError> {
+    recorded(|| {
+        app.state::<WindowState>()
+            .do_not_disturb
+            .store(enabled, Ordering::SeqCst);
+
+        if let Some(window) = app.get_webview_window("main") {
+            window.set_always_on_top(is_pinned_on_top(&app))?;
+        }
+        if let Some(tray_menu) = app.try_state::<TrayMenuState>() {
+            let _ = tray_menu.do_not_disturb_item.set_checked(enabled);
+        }
+
+        app.emit("do-not-disturb-changed", enabled).map_err(AppError::from)
+    })
+}
+
+/// Lets clicks pass through the main window, for using it as an overlay. The
+/// window stays on top meanwhile. Runtime-only; since the window itself can't be
+/// clicked, the click-through global shortcut is the way back out.
+#[tauri::command]
+fn set_click_through(app: tauri::AppHandle, enabled: bool) -> Result<(), AppError> {
+    recorded(|| {
+        app.state::<WindowState>()
+            .click_through
+            .store(enabled, Ordering::SeqCst);
+
+        if let Some(window) = app.get_webview_window("main") {
+            window.set_ignore_cursor_events(enabled)?;
+            window.set_always_on_top(is_pinned_on_top(&app))?;
+        }
+        if let Some(tray_menu) = app.try_state::<TrayMenuState>() {
+            let _ = tray_menu.click_through_item.set_checked(enabled);
+        }
+
+        app.emit("click-through-changed", enabled).map_err(AppError::from)
+    })
+}
+
+fn create_tray(app: &tauri::AppHandle, compact_enabled: bool) -> tauri::Result<()> {
+    let show_item = MenuItem::with_id(app, "show", "Show", true, None::<&str>)?;
+    let new_chat_item = MenuItem::with_id(app, "new_chat", "New Chat", true, None::<&str>)?;
+    let undo_new_chat_item = MenuItem::with_id(app, "undo_new_chat", "Undo New Chat", true, None::<&str>)?;
+    let append_item = MenuItem::with_id(app, "append_to_chat", "Append Clipboard to Chat", true, None::<&str>)?;
+    let compact_item = CheckMenuItem::with_id(app, "compact_mode", "Compact Mode", true, compact_enabled, None::<&str>)?;
+    let do_not_disturb_item = CheckMenuItem::with_id(app, "do_not_disturb", "Do Not Disturb", true, is_do_not_disturb(app), None::<&str>)?;
+    let click_through_item = CheckMenuItem::with_id(app, "click_through", "Click-Through", true, is_click_through(app), None::<&str>)?;
+    let settings_item = MenuItem::with_id(app, "settings", "Settings", true, None::<&str>)?;
+    let copy_config_path_item = MenuItem::with_id(app, "copy_config_path", "Copy Config Path", true, None::<&str>)?;
+    let quit_item = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+
+    let menu = Menu::with_items(app, &[&show_item, &new_chat_item, &undo_new_chat_item, &append_item, &compact_item, &do_not_disturb_item, &click_through_item, &settings_item, &copy_config_path_item, &quit_item])?;
+
+    app.manage(TrayMenuState {
+        compact_mode_item: compact_item.clone(),
+        do_not_disturb_item: do_not_disturb_item.clone(),
+        click_through_item: click_through_item.clone(),
+    });
+
+    let icon = app.default_window_icon().cloned().expect("no icon found");
+
+    TrayIconBuilder::new()
+        .icon(icon)
+        .menu(&menu)
+        .tooltip("AI Quick Access")
+        .on_menu_event(|app, event| match event.id.as_ref() {
+            "show" => {
+                create_or_focus_main_window(app, false);
+            }
+            "new_chat" => {
+                create_or_focus_main_window(app, true);
+            }
+            "undo_new_chat" => {
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = restore_last_cleared(app).await {
+                        eprintln!("Failed to undo new chat: {}", e);
+                    }
+                });
+            }
+            "append_to_chat" => {
+                if let Err(e) = append_clipboard_to_chat(app) {
+                    report_error(e.context("Failed to append clipboard to chat"));
+                }
+            }
+            "compact_mode" => {
+                // The menu toggles its own check mark before we get here
+                let enabled = app
+                    .state::<TrayMenuState>()
+                    .compact_mode_item
+                    .is_checked()
+                    .unwrap_or(false);
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = set_compact_mode(app, enabled).await {
+                        eprintln!("Failed to toggle compact mode: {}", e);
+                    }
+                });
+            }
+            "do_not_disturb" => {
+                let enabled = app
+                    .state::<TrayMenuState>()
+                    .do_not_disturb_item
+                    .is_checked()
+                    .unwrap_or(false);
+                if let Err(e) = set_do_not_disturb(app.clone(), enabled) {
+                    eprintln!("Failed to toggle do not disturb: {}", e);
+                }
+            }
+            "click_through" => {
+                let enabled = app
+                    .state::<TrayMenuState>()
+                    .click_through_item
+                    .is_checked()
+                    .unwrap_or(false);
+                if let Err(e) = set_click_through(app.clone(), enabled) {
+                    eprintln!("Failed to toggle click-through: {}", e);
+                }
+            }
+            "settings" => {
+                if let Some(window) = app.get_webview_window("settings") {
+                    let _ = window.show();
+                    let _ = window.set_focus();
+                }
+            }
+            "copy_config_path" => {
+                if let Err(e) = copy_config_path(app.clone()) {
+                    eprintln!("Failed to copy config path: {}", e);
+                }
+            }
+            "quit" => {
+                request_quit(app);
+            }
+            _ => {}
+        })
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                let app = tray.app_handle();
+                create_or_focus_main_window(app, false);
+            }
+        })
+        .build(app)?;
+
+    Ok(())
 }
 
-fn get_config_path() -> Result<PathBuf, String> {
-    let config_dir = dirs::config_dir().ok_or("Could not find config directory")?;
-    let app_config_dir = config_dir.join("ai-quick-access");
-    Ok(app_config_dir.join("configs.json"))
+/// Registers every default global shortcut. Failures are logged rather than
+/// fatal, and returned as self-test results.
+fn register_global_shortcuts(app: &tauri::AppHandle) -> Vec<SelfTestCheck> {
+    let shortcuts = default_shortcuts();
+    let settings = load_settings().unwrap_or_else(|_| default_settings());
+
+    let bindings = vec![
+        (shortcuts.focus, "focus shortcut (Ctrl/Cmd+E)", "focus"),
+        (shortcuts.new_chat, "new chat shortcut (Ctrl/Cmd+Shift+E)", "newChat"),
+        (shortcuts.scratch, "scratch shortcut (Ctrl/Cmd+Alt+E)", "scratch"),
+        (shortcuts.append_to_chat, "append to chat shortcut (Ctrl/Cmd+Alt+V)", "appendToChat"),
+        (shortcuts.click_through, "click-through shortcut (Ctrl/Cmd+Alt+T)", "clickThrough"),
+        (shortcuts.quick_reply, "quick reply shortcut (Ctrl/Cmd+Alt+R)", "quickReply"),
+        (ab_toggle_shortcut(&settings), "A/B model toggle shortcut", "abToggle"),
+    ];
+
+    bindings
+        .into_iter()
+        .filter(|(_, _, name)| is_shortcut_active(&settings, name))
+        .map(|(shortcut, description, name)| {
+            let result = app
+                .global_shortcut()
+                .register(shortcut)
+                .map_err(|e| AppError::Shortcut(format!("Failed to register {}: {}", description, e)));
+            SelfTestCheck::from_result(&format!("shortcut:{}", name), result)
+        })
+        .collect()
 }
 
-fn ensure_config_dir() -> Result<(), String> {
-    let config_path = get_config_path()?;
-    if let Some(parent) = config_path.parent() {
-        fs::create_dir_all(parent).map_err(|e| format!("Failed to create config directory: {}", e))?;
+/// Drops every global shortcut and registers the current set again, logging
+/// the ones that fail.
+fn reregister_global_shortcuts(app: &tauri::AppHandle) {
+    let _ = app.global_shortcut().unregister_all();
+    if app.state::<WindowState>().shortcuts_suspended.load(Ordering::SeqCst) {
+        return;
+    }
+    for check in register_global_shortcuts(app).iter().filter(|c| !c.ok) {
+        eprintln!("{}", check.error.as_deref().unwrap_or(&check.name));
+    }
+    // `unregister_all` dropped any one-shot shortcuts that haven't fired yet
+    for (shortcut, _) in app.state::<TemporaryShortcuts>().0.lock().unwrap().values() {
+        if let Err(e) = app.global_shortcut().register(*shortcut) {
+            eprintln!("Failed to restore temporary shortcut {}: {}", shortcut_to_string(shortcut), e);
+        }
     }
-    Ok(())
 }
 
-fn sync_launch_at_startup(app: &tauri::AppHandle, enable: bool) -> Result<(), String> {
-    let autolaunch = app.autolaunch();
-    let currently_enabled = autolaunch
-        .is_enabled()
-        .map_err(|e| format!("Failed to read launch at startup state: {}", e))?;
+const FULLSCREEN_POLL_INTERVAL: Duration = Duration::from_secs(2);
 
-    if enable && !currently_enabled {
-        autolaunch
-            .enable()
-            .map_err(|e| format!("Failed to enable launch at startup: {}", e))?;
-    } else if !enable && currently_enabled {
-        autolaunch
-            .disable()
-            .map_err(|e| format!("Failed to disable launch at startup: {}", e))?;
-    }
+// Whether the foreground window belongs to a fullscreen app, such as a game or
+// a presentation. Always false where this can't be detected (macOS, Wayland).
+#[cfg(target_os = "windows")]
+fn foreground_is_fullscreen() -> bool {
+    use windows::Win32::UI::Shell::{
+        SHQueryUserNotificationState, QUNS_BUSY, QUNS_PRESENTATION_MODE, QUNS_RUNNING_D3D_FULL_SCREEN,
+    };
 
-    Ok(())
+    unsafe { SHQueryUserNotificationState() }
+        .is_ok_and(|state| [QUNS_BUSY, QUNS_RUNNING_D3D_FULL_SCREEN, QUNS_PRESENTATION_MODE].contains(&state))
 }
 
-#[tauri::command]
-fn load_settings() -> Result<Settings, String> {
-    let config_path = get_config_path()?;
-    
-    if !config_path.exists() {
-        // Return default settings if file doesn't exist
-        let mut default_shortcuts = HashMap::new();
-        default_shortcuts.insert("h".to_string(), "google/gemini-3-pro-preview".to_string());
-        default_shortcuts.insert("f".to_string(), "google/gemini-3-flash-preview".to_string());
-        default_shortcuts.insert("o".to_string(), "openai/gpt-oss-120b".to_string());
-        return Ok(Settings {
-            api_key: String::new(),
-            selected_model: "openai/gpt-oss-120b".to_string(),
-            dark_mode: true,
-            auto_start: false,
-            system_prompt: "Keep your responses as concise, precise, to the point.\nAnswer the question in as few words as possible.\nNo Yapping.".to_string(),
-            model_shortcuts: default_shortcuts,
-            send_on_enter: false,
-        });
-    }
-    
-    let contents = fs::read_to_string(&config_path)
-        .map_err(|e| format!("Failed to read config file: {}", e))?;
-    
-    serde_json::from_str(&contents)
-        .map_err(|e| format!("Failed to parse config file: {}", e))
+#[cfg(target_os = "linux")]
+fn foreground_is_fullscreen() -> bool {
+    let xprop = |args: &[&str]| {
+        std::process::Command::new("xprop")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+
+    // "_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007"
+    let Some(active) = xprop(&["-root", "_NET_ACTIVE_WINDOW"]) else {
+        return false;
+    };
+    let Some(id) = active.split_whitespace().last().filter(|id| id.starts_with("0x") && *id != "0x0") else {
+        return false;
+    };
+    xprop(&["-id", id, "_NET_WM_STATE"]).is_some_and(|state| state.contains("_NET_WM_STATE_FULLSCREEN"))
 }
 
-#[tauri::command]
-fn save_settings(app: tauri::AppHandle, settings: Settings) -> Result<(), String> {
-    ensure_config_dir()?;
-    let config_path = get_config_path()?;
-    
-    let contents = serde_json::to_string_pretty(&settings)
-        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
-    
-    fs::write(&config_path, contents)
-        .map_err(|e| format!("Failed to write config file: {}", e))?;
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+fn foreground_is_fullscreen() -> bool {
+    false
+}
 
-    sync_launch_at_startup(&app, settings.auto_start)?;
-    
-    Ok(())
+/// Polls for fullscreen apps and unregisters the global shortcuts while one is
+/// in front. Only polls while `suspend_shortcuts_in_fullscreen` is on; otherwise
+/// it waits for `sync_suspend_in_fullscreen` to turn it on.
+fn watch_fullscreen_apps(app: &tauri::AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let state = app.state::<WindowState>();
+            let enabled = state.suspend_in_fullscreen.load(Ordering::SeqCst);
+            if enabled {
+                tokio::time::sleep(FULLSCREEN_POLL_INTERVAL).await;
+            }
+
+            let fullscreen = enabled && foreground_is_fullscreen();
+            let was_suspended = state.shortcuts_suspended.swap(fullscreen, Ordering::SeqCst);
+            if fullscreen != was_suspended {
+                eprintln!(
+                    "{} global shortcuts for a fullscreen app",
+                    if fullscreen { "Suspending" } else { "Restoring" }
+                );
+                reregister_global_shortcuts(&app);
+            }
+            if !enabled {
+                // A permit left by an earlier `notify_one` wakes this right away
+                state.fullscreen_poller_wake.notified().await;
+            }
+        }
+    });
 }
 
-// Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
-#[tauri::command]
-fn greet(name: &str) -> String {
-    format!("Hello, {}! You've been greeted from Rust!", name)
+// Names of the global shortcuts, as used by `get_default_shortcuts` and `shortcut_enabled`
+const SHORTCUT_NAMES: &[&str] = &["focus", "newChat", "scratch", "appendToChat", "quickReply", "clickThrough", "abToggle"];
+
+// Ctrl+Alt combinations are what AltGr sends on Windows, and desktops bind some
+// of them (Ctrl+Alt+T opens a terminal on GNOME), so these stay unbound until
+// switched on
+const OPT_IN_SHORTCUTS: &[&str] = &["scratch", "appendToChat", "clickThrough"];
+
+fn is_shortcut_enabled(settings: &Settings, name: &str) -> bool {
+    settings
+        .shortcut_enabled
+        .get(name)
+        .copied()
+        .unwrap_or(!OPT_IN_SHORTCUTS.contains(&name))
 }
 
-#[tauri::command]
-async fn open_settings(app: tauri::AppHandle) -> Result<(), String> {
-    if let Some(window) = app.get_webview_window("settings") {
-        window.show().map_err(|e| e.to_string())?;
-        window.set_focus().map_err(|e| e.to_string())?;
-    }
-    Ok(())
+// Whether the global shortcut `name` is live: switched on, and for quick reply
+// and the A/B toggle, only while that feature is actually on
+fn is_shortcut_active(settings: &Settings, name: &str) -> bool {
+    is_shortcut_enabled(settings, name)
+        && match name {
+            "quickReply" => settings.quick_reply_enabled,
+            "abToggle" => settings.ab_models.is_some(),
+            _ => true,
+        }
 }
 
+/// Turns the global shortcut `name` off or back on, keeping its binding.
 #[tauri::command]
-async fn quit_app(app: tauri::AppHandle) {
-    app.exit(0);
+async fn set_shortcut_enabled(app: tauri::AppHandle, name: String, enabled: bool) -> Result<(), AppError> {
+    recorded_async(async move {
+        if !SHORTCUT_NAMES.contains(&name.as_str()) {
+            return Err(AppError::NotFound(format!("No shortcut named '{}'", name)));
+        }
+
+        let mut settings = load_settings()?;
+        settings.shortcut_enabled.insert(name, enabled);
+        write_settings(&settings)?;
+
+        reregister_global_shortcuts(&app);
+        app.emit("settings-changed", &settings).map_err(AppError::from)
+    })
+    .await
 }
 
+/// Re-reads `configs.json` after an external edit and re-applies everything
+/// that depends on it. A file that fails to parse leaves the app untouched.
 #[tauri::command]
-async fn resize_window(app: tauri::AppHandle, height_percentage: f64) -> Result<(), String> {
-    if let Some(window) = app.get_webview_window("main") {
-        if let Ok(Some(monitor)) = window.current_monitor() {
-            let size = monitor.size();
-            let new_height = (size.height as f64 * height_percentage).round() as u32;
-            let current_size = window.inner_size().map_err(|e| e.to_string())?;
-            
-            // Resize
-            window.set_size(tauri::Size::Physical(tauri::PhysicalSize {
-                width: current_size.width,
-                height: new_height,
-            })).map_err(|e| e.to_string())?;
-            
-            // Center
-            let monitor_pos = monitor.position();
-            let monitor_size = monitor.size();
-            
-            let x = monitor_pos.x + ((monitor_size.width as i32 - current_size.width as i32) / 2);
-            let y = monitor_pos.y + ((monitor_size.height as i32 - new_height as i32) / 2);
-            
-            window.set_position(tauri::Position::Physical(tauri::PhysicalPosition {
-                x,
-                y
-            })).map_err(|e| e.to_string())?;
-        }
-    }
+fn reload_settings(app: tauri::AppHandle) -> Result<Settings, AppError> {
+    recorded(|| {
+        let settings = load_settings()?;
+
+        reregister_global_shortcuts(&app);
+
+        sync_launch_at_startup(&app, settings.auto_start)?;
+        sync_compact_mode_item(&app, settings.compact_mode);
+        sync_suspend_in_fullscreen(&app, settings.suspend_shortcuts_in_fullscreen);
+        let _ = app.emit("settings-changed", &settings);
+
+        Ok(settings)
+    })
+}
+
+/// Flips `selected_model` to the other of `ab_models`. A model that is neither
+/// of the two switches to the first.
+fn toggle_ab_model(app: &tauri::AppHandle) -> Result<(), AppError> {
+    let mut settings = load_settings()?;
+    let Some((first, second)) = settings.ab_models.clone() else {
+        return Err(AppError::Config("No A/B models configured".to_string()));
+    };
+
+    settings.selected_model = if settings.selected_model == first { second } else { first };
+    write_settings(&settings)?;
+    app.emit("settings-changed", &settings)?;
+
+    notify_response_ready(app, &format!("Switched to {}", settings.selected_model));
     Ok(())
 }
 
-#[tauri::command]
-async fn reset_window(app: tauri::AppHandle) -> Result<(), String> {
-    if let Some(window) = app.get_webview_window("main") {
-        // Set size to 800x150
-        window.set_size(tauri::Size::Logical(tauri::LogicalSize {
-            width: 800.0,
-            height: 150.0,
-        })).map_err(|e| e.to_string())?;
-        
-        // Center
-        if let Ok(Some(monitor)) = window.current_monitor() {
-             let monitor_pos = monitor.position();
-             let monitor_size = monitor.size();
-             let scale_factor = monitor.scale_factor();
-             let width_physical = (800.0 * scale_factor) as i32;
-             let height_physical = (150.0 * scale_factor) as i32;
-             
-             let x = monitor_pos.x + ((monitor_size.width as i32 - width_physical) / 2);
-             let y = monitor_pos.y + ((monitor_size.height as i32 - height_physical) / 2);
-             
-             window.set_position(tauri::Position::Physical(tauri::PhysicalPosition {
-                 x,
-                 y
-             })).map_err(|e| e.to_string())?;
+/// Records a press of the focus shortcut and returns whether it completes a
+/// double press, with `double_press_new_chat` on.
+fn is_focus_double_press(app: &tauri::AppHandle) -> bool {
+    let settings = load_settings().unwrap_or_else(|_| default_settings());
+    let now = Instant::now();
+    let window_state = app.state::<WindowState>();
+    let mut last_press = window_state.last_focus_press.lock().unwrap();
+    let previous = last_press.replace(now);
+
+    if !settings.double_press_new_chat {
+        return false;
+    }
+    match previous {
+        Some(previous) if now.duration_since(previous) <= Duration::from_millis(settings.double_press_ms) => {
+            // A third quick press counts as the start of a new double press
+            *last_press = None;
+            true
         }
+        _ => false,
     }
-    Ok(())
 }
 
-fn get_mouse_position() -> Option<(i32, i32)> {
-    match Mouse::get_mouse_position() {
-        Mouse::Position { x, y } => Some((x, y)),
-        Mouse::Error => None,
+/// Runs the action bound to `shortcut`, or returns `None` if nothing is bound to it.
+fn run_shortcut_action(app: &tauri::AppHandle, shortcut: &Shortcut) -> Option<Result<(), AppError>> {
+    let shortcuts = default_shortcuts();
+
+    if shortcut == &shortcuts.focus {
+        // Focus window (or create if not exists); a quick second press starts a new chat
+        create_or_focus_main_window(app, is_focus_double_press(app));
+        Some(Ok(()))
+    } else if shortcut == &shortcuts.new_chat {
+        // Focus and start new chat
+        create_or_focus_main_window(app, true);
+        Some(Ok(()))
+    } else if shortcut == &shortcuts.scratch {
+        Some(toggle_scratch(app).map_err(|e| e.context("Failed to toggle scratch window")))
+    } else if shortcut == &shortcuts.append_to_chat {
+        Some(append_clipboard_to_chat(app).map_err(|e| e.context("Failed to append clipboard to chat")))
+    } else if shortcut == &shortcuts.click_through {
+        Some(set_click_through(app.clone(), !is_click_through(app)))
+    } else if shortcut == &ab_toggle_shortcut(&load_settings().unwrap_or_else(|_| default_settings())) {
+        // Off the main thread, since saving the switch may wait out a locked config
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = toggle_ab_model(&app) {
+                report_error(e.context("Failed to toggle A/B model"));
+            }
+        });
+        Some(Ok(()))
+    } else if shortcut == &shortcuts.quick_reply {
+        // Runs in the background so the shortcut handler isn't blocked on the request
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = quick_reply(app.clone()).await {
+                let e = e.context("Quick reply failed");
+                notify_response_ready(&app, &e.to_string());
+                report_error(e);
+            }
+        });
+        Some(Ok(()))
+    } else {
+        None
     }
 }
 
-fn center_window_on_monitor_with_mouse(app: &tauri::AppHandle, window: &tauri::WebviewWindow) {
-    // Get mouse position
-    let mouse_pos = match get_mouse_position() {
-        Some(pos) => pos,
-        None => return, // Fall back to default behavior if we can't get mouse position
-    };
+/// One-shot shortcuts from `register_temp_shortcut`, by shortcut id, with the
+/// name of the action each one runs.
+#[derive(Default)]
+struct TemporaryShortcuts(Mutex<HashMap<u32, (Shortcut, String)>>);
 
-    // Find the monitor that contains the mouse cursor
-    let monitors = match app.available_monitors() {
-        Ok(m) => m,
-        Err(_) => return,
-    };
+// The shortcut whose action `name` (one of `SHORTCUT_NAMES`) stands for
+fn shortcut_for_action(name: &str) -> Option<Shortcut> {
+    let shortcuts = default_shortcuts();
+    match name {
+        "focus" => Some(shortcuts.focus),
+        "newChat" => Some(shortcuts.new_chat),
+        "scratch" => Some(shortcuts.scratch),
+        "appendToChat" => Some(shortcuts.append_to_chat),
+        "quickReply" => Some(shortcuts.quick_reply),
+        "clickThrough" => Some(shortcuts.click_through),
+        "abToggle" => Some(ab_toggle_shortcut(&load_settings().unwrap_or_else(|_| default_settings()))),
+        _ => None,
+    }
+}
 
-    for monitor in monitors {
-        let pos = monitor.position();
-        let size = monitor.size();
-        
-        let monitor_x = pos.x;
-        let monitor_y = pos.y;
-        let monitor_width = size.width as i32;
-        let monitor_height = size.height as i32;
+/// Binds `accelerator` to the action `action_id` for a single press, after
+/// which it is unregistered again. Fails if the shortcut is already taken.
+#[tauri::command]
+fn register_temp_shortcut(
+    app: tauri::AppHandle,
+    temporary: tauri::State<'_, TemporaryShortcuts>,
+    accelerator: String,
+    action_id: String,
+) -> Result<(), AppError> {
+    recorded(|| {
+        if shortcut_for_action(&action_id).is_none() {
+            return Err(AppError::NotFound(format!("No action named '{}'", action_id)));
+        }
+        let shortcut = parse_shortcut(&validate_shortcut(accelerator)?)?;
+        if app.global_shortcut().is_registered(shortcut) {
+            return Err(AppError::Shortcut(format!(
+                "Shortcut '{}' is already in use",
+                shortcut_to_string(&shortcut)
+            )));
+        }
 
-        // Check if mouse is within this monitor
-        if mouse_pos.0 >= monitor_x
-            && mouse_pos.0 < monitor_x + monitor_width
-            && mouse_pos.1 >= monitor_y
-            && mouse_pos.1 < monitor_y + monitor_height
-        {
-            // Get window size
-            if let Ok(window_size) = window.outer_size() {
-                let window_width = window_size.width as i32;
-                let window_height = window_size.height as i32;
+        app.global_shortcut()
+            .register(shortcut)
+            .map_err(|e| AppError::Shortcut(format!("Failed to register '{}': {}", shortcut_to_string(&shortcut), e)))?;
+        temporary.0.lock().unwrap().insert(shortcut.id(), (shortcut, action_id));
+        Ok(())
+    })
+}
 
-                // Center the window on this monitor
-                let x = monitor_x + (monitor_width - window_width) / 2;
-                let y = monitor_y + (monitor_height - window_height) / 2;
+// Runs and forgets the one-shot shortcut `shortcut`, if it is one
+fn run_temp_shortcut(app: &tauri::AppHandle, shortcut: &Shortcut) -> Option<Result<(), AppError>> {
+    let (_, action_id) = app.state::<TemporaryShortcuts>().0.lock().unwrap().remove(&shortcut.id())?;
 
-                let _ = window.set_position(PhysicalPosition::new(x, y));
-            }
-            break;
+    // Unregistering from inside the shortcut handler can deadlock the plugin
+    let (handle, shortcut) = (app.clone(), *shortcut);
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = handle.global_shortcut().unregister(shortcut) {
+            eprintln!("Failed to unregister temporary shortcut: {}", e);
         }
-    }
+    });
+
+    let action = shortcut_for_action(&action_id)?;
+    run_shortcut_action(app, &action)
 }
 
-fn create_or_focus_main_window(app: &tauri::AppHandle, new_chat: bool) {
-    if let Some(window) = app.get_webview_window("main") {
-        // Move window to the monitor where the mouse is
-        center_window_on_monitor_with_mouse(app, &window);
-        // Show and bring to front
-        let _ = window.show();
-        let _ = window.set_focus();
-        // Set always on top temporarily to ensure it's above all other windows
-        let _ = window.set_always_on_top(true);
-        // Then disable always on top so it behaves normally after
-        let _ = window.set_always_on_top(false);
-        if new_chat {
-            let _ = app.emit("new-chat", ());
+/// Lets the settings page try a binding without pressing the global keys, which
+/// another app may have grabbed.
+#[tauri::command]
+async fn trigger_action_for_shortcut(app: tauri::AppHandle, accelerator: String) -> Result<(), AppError> {
+    recorded_async(async move {
+        let shortcut = parse_shortcut(&accelerator)?;
+        // Same gating as registration, so a switched-off action can't be run from here
+        let settings = load_settings()?;
+        let inactive = SHORTCUT_NAMES
+            .iter()
+            .find(|name| shortcut_for_action(name) == Some(shortcut))
+            .filter(|name| !is_shortcut_active(&settings, name));
+        if inactive.is_some() {
+            return Err(AppError::Shortcut(format!(
+                "Shortcut '{}' is switched off",
+                shortcut_to_string(&shortcut)
+            )));
         }
-    } else {
-        // Create a new main window if it doesn't exist
-        #[allow(unused_variables)]
-        if let Ok(window) = WebviewWindowBuilder::new(
-            app,
-            "main",
-            WebviewUrl::App("/".into()),
-        )
-        .title("ai-quick-access")
-        .inner_size(800.0, 150.0)
-        .decorations(false)
-        .build()
-        {
-            // Position window on the monitor where the mouse is
-            center_window_on_monitor_with_mouse(app, &window);
-            
-            // Bring to front
-            let _ = window.set_always_on_top(true);
-            let _ = window.set_always_on_top(false);
 
-            #[cfg(target_os = "macos")]
-            {
-                let _ = window_vibrancy::apply_vibrancy(
-                    &window,
-                    window_vibrancy::NSVisualEffectMaterial::UnderWindowBackground,
-                    None,
-                    Some(10.0),
-                );
-            }
+        run_shortcut_action(&app, &shortcut)
+            .unwrap_or_else(|| {
+                Err(AppError::Shortcut(format!(
+                    "Shortcut '{}' is not bound to any action",
+                    shortcut_to_string(&shortcut)
+                )))
+            })
+    })
+    .await
+}
 
-            #[cfg(target_os = "windows")]
-            {
-                let _ = window_vibrancy::apply_blur(&window, Some((18, 18, 18, 125)));
-            }
+/// Does what pressing Ctrl/Cmd+E does, for onboarding to demonstrate.
+#[tauri::command]
+async fn simulate_focus_shortcut(app: tauri::AppHandle) -> Result<(), AppError> {
+    recorded_async(async move {
+        run_shortcut_action(&app, &default_shortcuts().focus).unwrap_or(Ok(()))
+    })
+    .await
+}
 
-            if new_chat {
-                let _ = app.emit("new-chat", ());
-            }
-        }
-    }
+/// Does what pressing Ctrl/Cmd+Shift+E does, for onboarding to demonstrate.
+#[tauri::command]
+async fn simulate_new_chat_shortcut(app: tauri::AppHandle) -> Result<(), AppError> {
+    recorded_async(async move {
+        run_shortcut_action(&app, &default_shortcuts().new_chat).unwrap_or(Ok(()))
+    })
+    .await
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let process_start = Instant::now();
+    let launch_autostart_args = autostart_args(load_settings().map(|s| s.start_hidden).unwrap_or(true));
+
     tauri::Builder::default()
+        .manage(StartupTimings {
+            process_start,
+            setup_done: Mutex::new(None),
+            first_show: Mutex::new(None),
+        })
+        .manage(WindowState::default())
+        .manage(ConfigWatcher::default())
+        .manage(ModelCapabilities::default())
+        .manage(CreditsCache::default())
+        .manage(SystemPromptCache::default())
+        .manage(SessionSystemPrompt::default())
+        .manage(ClearedConversation::default())
+        .manage(FanOutCancel::default())
+        .manage(TemporaryShortcuts::default())
+        .manage(AutostartArgs(launch_autostart_args.clone()))
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_notification::init())
         .plugin(
             tauri_plugin_global_shortcut::Builder::new()
-                .with_handler(|app, shortcut, _event| {
-                    #[cfg(target_os = "macos")]
-                    let mod_key = Modifiers::SUPER;
-                    #[cfg(not(target_os = "macos"))]
-                    let mod_key = Modifiers::CONTROL;
-
-                    let focus_shortcut = Shortcut::new(Some(mod_key), Code::KeyE);
-                    let new_chat_shortcut = Shortcut::new(Some(mod_key | Modifiers::SHIFT), Code::KeyE);
-
-                    if shortcut == &focus_shortcut {
-                        // Focus window (or create if not exists)
-                        create_or_focus_main_window(app, false);
-                    } else if shortcut == &new_chat_shortcut {
-                        // Focus and start new chat
-                        create_or_focus_main_window(app, true);
+                .with_handler(|app, shortcut, event| {
+                    // Releasing the keys fires the handler too
+                    if event.state() != ShortcutState::Pressed {
+                        return;
+                    }
+                    let result = run_temp_shortcut(app, shortcut).or_else(|| run_shortcut_action(app, shortcut));
+                    if let Some(Err(e)) = result {
+                        report_error(e);
                     }
                 })
                 .build(),
         )
             .plugin(tauri_plugin_autostart::init(
                 MacosLauncher::LaunchAgent,
-                None,
+                Some(launch_autostart_args),
             ))
-        .invoke_handler(tauri::generate_handler![greet, open_settings, load_settings, save_settings, quit_app, resize_window, reset_window])
+        .invoke_handler(tauri::generate_handler![greet, open_settings, load_settings, save_settings, reload_settings, set_config_directory, repair_config_dir, copy_config_path, quit_app, quit_confirmed, resize_window, resize_window_animated, reset_window, set_layout_mode, get_layout_mode, validate_shortcut, check_shortcut_conflicts, format_shortcut_for_display, set_shortcut_enabled, trigger_action_for_shortcut, register_temp_shortcut, simulate_focus_shortcut, simulate_new_chat_shortcut, load_scratch, save_scratch, toggle_scratch_window, move_window_to_monitor, get_window_geometry, set_window_title, record_chat_interaction, normalize_conversation, stash_cleared_conversation, restore_last_cleared, preview_window_anchor, get_default_shortcuts, set_compact_mode, set_accent_color, set_do_not_disturb, set_click_through, set_blur_tint, check_connectivity, fetch_available_models, resolve_model, analyze_system_prompt, get_effective_system_prompt, reload_system_prompt, set_session_system_prompt, benchmark_model, fan_out_chat, cancel_fan_out, validate_request_overrides, get_credits, list_api_keys, add_api_key, remove_api_key, set_active_api_key, clear_api_key, open_external_url, open_preview_window, export_diagnostics, settings_diff, export_settings_encrypted, import_settings_encrypted, export_state_snapshot, import_state_snapshot, get_autostart_args, get_startup_diagnostics, get_startup_timings, get_recent_errors, clear_recent_errors, get_platform_capabilities, get_effects_support, get_recent_prompts, add_recent_prompt, clear_recent_prompts, recompress_storage, send_chat_message])
+        .on_window_event(|window, event| match event {
+            // The scratch window is long-lived: closing it only hides it so its
+            // content and position survive until the next toggle.
+            tauri::WindowEvent::CloseRequested { api, .. } if window.label() == "scratch" => {
+                api.prevent_close();
+                let _ = window.hide();
+            }
+            // Leaving the window counts too, so idle time starts when the user walks away
+            tauri::WindowEvent::Focused(_) if window.label() == "main" => {
+                record_interaction(window.app_handle());
+            }
+            tauri::WindowEvent::Moved(_) | tauri::WindowEvent::Resized(_) if window.label() == "main" => {
+                schedule_geometry_event(window);
+            }
+            _ => {}
+        })
         .setup(|app| {
+            let http_client = build_http_client(app.handle())?;
+            app.manage(HttpClient(http_client));
+
             match load_settings() {
                 Ok(settings) => {
                     let app_handle = app.handle();
                     if let Err(err) = sync_launch_at_startup(&app_handle, settings.auto_start) {
                         eprintln!("Failed to sync launch at startup setting: {}", err);
                     }
+                    sync_suspend_in_fullscreen(app_handle, settings.suspend_shortcuts_in_fullscreen);
+                    if let Err(err) = refresh_autostart_registration(&app_handle, settings.auto_start) {
+                        eprintln!("{}", err);
+                    }
                 }
                 Err(err) => {
                     eprintln!("Failed to load settings during startup sync: {}", err);
                 }
             }
 
-            // Register global shortcuts based on OS
-            #[cfg(target_os = "macos")]
-            let mod_key = Modifiers::SUPER;
-            #[cfg(not(target_os = "macos"))]
-            let mod_key = Modifiers::CONTROL;
+            // Self-test: can we actually persist settings?
+            let mut checks = vec![
+                SelfTestCheck::from_result("configDir", ensure_config_dir()),
+                SelfTestCheck::from_result("configWrite", check_config_dir_round_trip()),
+            ];
 
-            let focus_shortcut = Shortcut::new(Some(mod_key), Code::KeyE);
-            let new_chat_shortcut = Shortcut::new(Some(mod_key | Modifiers::SHIFT), Code::KeyE);
+            // Register global shortcuts based on OS
+            let shortcut_checks = register_global_shortcuts(app.handle());
+            let registered_shortcuts = shortcut_checks.iter().filter(|c| c.ok).count();
+            checks.extend(shortcut_checks);
+            watch_fullscreen_apps(app.handle());
 
-            // Try to register shortcuts, log errors but don't fail
-            if let Err(e) = app.global_shortcut().register(focus_shortcut) {
-                eprintln!("Failed to register focus shortcut (Ctrl/Cmd+E): {}", e);
+            for check in checks.iter().filter(|c| !c.ok) {
+                eprintln!("Startup self-test '{}' failed: {}", check.name, check.error.as_deref().unwrap_or(""));
             }
-            if let Err(e) = app.global_shortcut().register(new_chat_shortcut) {
-                eprintln!("Failed to register new chat shortcut (Ctrl/Cmd+Shift+E): {}", e);
+            if !platform_capabilities().window_positioning {
+                eprintln!("Running under Wayland: the compositor may ignore window positioning and global shortcuts");
             }
+            let diagnostics = StartupDiagnostics {
+                all_passed: checks.iter().all(|c| c.ok),
+                checks,
+            };
+            // No webview has loaded yet, so pages ask via `get_startup_diagnostics`
+            app.manage(diagnostics);
 
-            // Create system tray
-            let show_item = MenuItem::with_id(app, "show", "Show", true, None::<&str>)?;
-            let new_chat_item = MenuItem::with_id(app, "new_chat", "New Chat", true, None::<&str>)?;
-            let settings_item = MenuItem::with_id(app, "settings", "Settings", true, None::<&str>)?;
-            let quit_item = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+            let settings = load_settings().unwrap_or_else(|_| default_settings());
+            let show_tray_icon = settings.show_tray_icon;
 
-            let menu = Menu::with_items(app, &[&show_item, &new_chat_item, &settings_item, &quit_item])?;
+            if let Err(e) = sync_config_watcher(app.handle(), settings.watch_config) {
+                eprintln!("{}", e);
+            }
+            let compact_enabled = settings.compact_mode;
 
-            let icon = app.default_window_icon().cloned().expect("no icon found");
+            // Create system tray, unless the user opted out. Without a tray the
+            // global shortcuts are the only way in, so keep it if none registered.
+            if show_tray_icon || registered_shortcuts == 0 {
+                if !show_tray_icon {
+                    eprintln!("Tray icon is disabled but no global shortcut could be registered; showing the tray anyway so the app stays reachable");
+                }
+                create_tray(app.handle(), compact_enabled)?;
+            }
 
-            let _tray = TrayIconBuilder::new()
-                .icon(icon)
-                .menu(&menu)
-                .tooltip("AI Quick Access")
-                .on_menu_event(|app, event| match event.id.as_ref() {
-                    "show" => {
-                        create_or_focus_main_window(app, false);
-                    }
-                    "new_chat" => {
-                        create_or_focus_main_window(app, true);
-                    }
-                    "settings" => {
-                        if let Some(window) = app.get_webview_window("settings") {
-                            let _ = window.show();
-                            let _ = window.set_focus();
-                        }
-                    }
-                    "quit" => {
-                        app.exit(0);
-                    }
-                    _ => {}
-                })
-                .on_tray_icon_event(|tray, event| {
-                    if let TrayIconEvent::Click {
-                        button: MouseButton::Left,
-                        button_state: MouseButtonState::Up,
-                        ..
-                    } = event
-                    {
-                        let app = tray.app_handle();
-                        create_or_focus_main_window(app, false);
-                    }
-                })
-                .build(app)?;
+            if settings.prefetch_on_launch && !settings.api_key.is_empty() {
+                prefetch_models(app.handle(), settings.api_key.clone());
+            }
+
+            // Build the main window up front so the first summon doesn't wait
+            // for the webview to start
+            if settings.preload_window {
+                if let Err(e) = build_main_window(app.handle(), true) {
+                    eprintln!("Failed to preload main window: {}", e);
+                }
+            }
 
-            // Apply vibrancy to main window
-            #[allow(unused_variables)]
-            if let Some(main_window) = app.get_webview_window("main") {
-                #[cfg(target_os = "macos")]
-                window_vibrancy::apply_vibrancy(
-                    &main_window,
-                    window_vibrancy::NSVisualEffectMaterial::UnderWindowBackground,
-                    None,
-                    Some(10.0),
-                )
-                .expect("Failed to apply vibrancy to main window");
+            // Only the login item passes `--hidden`; starting the app by hand opens the window
+            let launched_hidden = std::env::args().any(|arg| arg == HIDDEN_ARG);
+            if !launched_hidden {
+                create_or_focus_main_window(app.handle(), false);
+            }
 
-                #[cfg(target_os = "windows")]
-                window_vibrancy::apply_blur(&main_window, Some((18, 18, 18, 125)))
-                    .expect("Failed to apply blur to main window");
+            let timings = app.state::<StartupTimings>();
+            *timings.setup_done.lock().unwrap() = Some(Instant::now());
+            if let Some(ms) = startup_timings_report(&timings).setup_ms {
+                eprintln!("Setup finished {}ms after start", ms);
             }
 
             Ok(())
@@ -417,3 +5030,337 @@ pub fn run() {
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(value: serde_json::Value) -> Vec<serde_json::Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn strip_json_leniency_removes_comments_and_trailing_commas() {
+        let lenient = r#"{
+            // line comment
+            "a": [1, 2,],
+            /* block */ "b": {"c": true,},
+        }"#;
+        let strict: serde_json::Value = serde_json::from_str(&strip_json_leniency(lenient)).unwrap();
+        assert_eq!(strict, serde_json::json!({ "a": [1, 2], "b": { "c": true } }));
+    }
+
+    #[test]
+    fn strip_json_leniency_leaves_strings_alone() {
+        let contents = r#"{"url": "https://example.com/*x*/", "quote": "a \"//\" b,}"}"#;
+        assert_eq!(strip_json_leniency(contents), contents);
+    }
+
+    #[test]
+    fn normalize_conversation_trims_drops_and_merges() {
+        let normalized = normalize_conversation(messages(serde_json::json!([
+            { "role": "user", "content": "  first " },
+            { "role": "user", "content": "   " },
+            { "role": "user", "content": "second" },
+            { "role": "assistant", "content": "reply" },
+        ])));
+        assert_eq!(
+            normalized,
+            messages(serde_json::json!([
+                { "role": "user", "content": "first\n\nsecond" },
+                { "role": "assistant", "content": "reply" },
+            ]))
+        );
+    }
+
+    #[test]
+    fn normalize_conversation_drops_repeated_messages() {
+        let normalized = normalize_conversation(messages(serde_json::json!([
+            { "role": "user", "content": "a" },
+            { "role": "user", "content": "b" },
+            { "role": "user", "content": "b " },
+        ])));
+        assert_eq!(normalized, messages(serde_json::json!([{ "role": "user", "content": "a\n\nb" }])));
+    }
+
+    #[test]
+    fn normalize_conversation_passes_non_text_content_through() {
+        let parts = serde_json::json!([
+            { "role": "user", "content": "look" },
+            { "role": "user", "content": [{ "type": "image_url", "image_url": { "url": "data:" } }] },
+        ]);
+        assert_eq!(normalize_conversation(messages(parts.clone())), messages(parts));
+    }
+
+    #[test]
+    fn resolve_model_id_maps_aliases_case_insensitively() {
+        let mut settings = default_settings();
+        settings.model_aliases.insert("fast".to_string(), "openai/gpt-4o-mini".to_string());
+        assert_eq!(resolve_model_id(&settings, " Fast "), "openai/gpt-4o-mini");
+        assert_eq!(resolve_model_id(&settings, "anthropic/claude-3.5-sonnet"), "anthropic/claude-3.5-sonnet");
+    }
+
+    #[test]
+    fn encrypted_settings_round_trip() {
+        let mut settings = default_settings();
+        settings.api_key = "sk-or-test".to_string();
+        let container = encrypt_settings(&settings, "correct horse").unwrap();
+
+        let decrypted = decrypt_settings(&container, "correct horse").unwrap();
+        assert_eq!(serde_json::to_value(&decrypted).unwrap(), serde_json::to_value(&settings).unwrap());
+        assert!(matches!(decrypt_settings(&container, "wrong"), Err(AppError::Auth(_))));
+    }
+
+    #[test]
+    fn encrypt_settings_rejects_empty_passphrase() {
+        assert!(matches!(encrypt_settings(&default_settings(), ""), Err(AppError::Config(_))));
+    }
+
+    #[test]
+    fn validate_app_referer_accepts_http_urls_only() {
+        assert!(validate_app_referer("").is_ok());
+        assert!(validate_app_referer("https://example.com").is_ok());
+        assert!(validate_app_referer("example.com").is_err());
+        assert!(validate_app_referer("ftp://example.com").is_err());
+    }
+
+    #[test]
+    fn normalize_accent_color_uppercases_hex() {
+        assert_eq!(normalize_accent_color(" #a1b2c3 ").unwrap(), "#A1B2C3");
+        assert_eq!(normalize_accent_color("").unwrap(), "");
+        assert!(normalize_accent_color("#abc").is_err());
+        assert!(normalize_accent_color("#ggggggg").is_err());
+    }
+
+    #[test]
+    fn normalize_stop_sequences_drops_blank_and_caps_count() {
+        let sequences = ["END".to_string(), " ".to_string()];
+        assert_eq!(normalize_stop_sequences(&sequences).unwrap(), vec!["END".to_string()]);
+        let too_many = vec!["x".to_string(); MAX_STOP_SEQUENCES + 1];
+        assert!(normalize_stop_sequences(&too_many).is_err());
+    }
+
+    #[test]
+    fn normalize_custom_headers_rejects_reserved_and_invalid_headers() {
+        let headers = HashMap::from([(" X-Team ".to_string(), " a ".to_string()), ("".to_string(), "x".to_string())]);
+        assert_eq!(
+            normalize_custom_headers(&headers).unwrap(),
+            HashMap::from([("X-Team".to_string(), "a".to_string())])
+        );
+        let reserved = HashMap::from([("Authorization".to_string(), "Bearer x".to_string())]);
+        assert!(normalize_custom_headers(&reserved).is_err());
+        let invalid = HashMap::from([("Bad Header".to_string(), "x".to_string())]);
+        assert!(normalize_custom_headers(&invalid).is_err());
+    }
+
+    #[test]
+    fn validate_shortcut_rejects_empty_input() {
+        assert!(matches!(validate_shortcut(String::new()), Err(AppError::Shortcut(_))));
+        assert!(matches!(validate_shortcut("  ".to_string()), Err(AppError::Shortcut(_))));
+        assert!(validate_shortcut("Ctrl++E".to_string()).is_err());
+    }
+
+    #[test]
+    fn validate_shortcut_accepts_function_keys_with_a_modifier() {
+        assert_eq!(validate_shortcut("Ctrl+F5".to_string()).unwrap(), "Control+F5");
+        assert_eq!(validate_shortcut("Shift+F12".to_string()).unwrap(), "Shift+F12");
+        assert!(validate_shortcut("F5".to_string()).is_err());
+    }
+
+    #[test]
+    fn validate_shortcut_understands_os_modifier_names() {
+        assert_eq!(validate_shortcut("Win+K".to_string()).unwrap(), "Super+KeyK");
+        assert_eq!(validate_shortcut("meta+k".to_string()).unwrap(), "Super+KeyK");
+        assert_eq!(validate_shortcut("Ctl+Shift+K".to_string()).unwrap(), "Control+Shift+KeyK");
+    }
+
+    #[test]
+    fn validate_shortcut_rejects_modifier_only_input() {
+        assert!(validate_shortcut("Ctrl".to_string()).is_err());
+        assert!(validate_shortcut("Ctrl+Shift".to_string()).is_err());
+    }
+
+    #[test]
+    fn validate_shortcut_rejects_shift_only_printable_keys() {
+        assert!(validate_shortcut("Shift+A".to_string()).is_err());
+        assert!(validate_shortcut("Shift+1".to_string()).is_err());
+    }
+
+    #[test]
+    fn validate_shortcut_rejects_reserved_combos() {
+        assert!(validate_shortcut("Alt+F4".to_string()).is_err());
+        assert!(validate_shortcut("ctrl+alt+delete".to_string()).is_err());
+        assert!(validate_shortcut("Win+L".to_string()).is_err());
+    }
+
+    #[test]
+    fn normalize_provider_preferences_trims_and_checks_names() {
+        let mut prefs: ProviderPrefs =
+            serde_json::from_value(serde_json::json!({ "order": [" Anthropic ", "", "Together"] })).unwrap();
+        normalize_provider_preferences(&mut prefs).unwrap();
+        assert_eq!(prefs.order, vec!["Anthropic".to_string(), "Together".to_string()]);
+
+        let mut invalid: ProviderPrefs = serde_json::from_value(serde_json::json!({ "order": ["a;b"] })).unwrap();
+        assert!(normalize_provider_preferences(&mut invalid).is_err());
+    }
+
+    #[test]
+    fn split_command_line_keeps_quoted_parts_together() {
+        assert_eq!(
+            split_command_line(r#"notify-send "Reply ready" 'a  b' "" x"#).unwrap(),
+            vec!["notify-send", "Reply ready", "a  b", "", "x"]
+        );
+        assert_eq!(split_command_line("tee a|b > out").unwrap(), vec!["tee", "a|b", ">", "out"]);
+    }
+
+    #[test]
+    fn split_command_line_rejects_empty_and_unterminated_commands() {
+        assert!(matches!(split_command_line("   "), Err(AppError::Config(_))));
+        assert!(matches!(split_command_line("say \"hi"), Err(AppError::Config(_))));
+    }
+
+    #[test]
+    fn find_shortcut_conflicts_flags_unusable_keys() {
+        let shortcuts: HashMap<String, String> = ["Enter", "H", "a-b", "h", "ok", "s"]
+            .iter()
+            .map(|key| (key.to_string(), "openai/gpt-oss-120b".to_string()))
+            .collect();
+        let conflicts: Vec<(String, String)> = find_shortcut_conflicts(&shortcuts)
+            .into_iter()
+            .map(|c| (c.key, c.conflicts_with))
+            .collect();
+        assert_eq!(
+            conflicts,
+            vec![
+                ("Enter".to_string(), "Enter".to_string()),
+                ("a-b".to_string(), String::new()),
+                ("h".to_string(), "H".to_string()),
+                ("s".to_string(), "/s".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn find_shortcut_conflicts_accepts_distinct_keys() {
+        let shortcuts = HashMap::from([("g".to_string(), "x".to_string()), ("fast_1".to_string(), "y".to_string())]);
+        assert!(find_shortcut_conflicts(&shortcuts).is_empty());
+    }
+
+    #[test]
+    fn shortcut_display_string_follows_platform_conventions() {
+        let display = |accelerator: &str| shortcut_display_string(&parse_shortcut(accelerator).unwrap());
+        if cfg!(target_os = "macos") {
+            assert_eq!(display("Ctrl+Shift+E"), "⌃⇧E");
+            assert_eq!(display("Cmd+Alt+ArrowUp"), "⌥⌘↑");
+        } else {
+            let super_name = if cfg!(target_os = "windows") { "Win" } else { "Super" };
+            assert_eq!(display("Ctrl+Shift+E"), "Ctrl+Shift+E");
+            assert_eq!(display("Super+Alt+ArrowUp"), format!("Alt+{}+Up", super_name));
+            assert_eq!(display("Ctrl+Digit1"), "Ctrl+1");
+        }
+        assert!(display("Alt+F5").ends_with("F5"));
+    }
+
+    #[test]
+    fn format_utc_timestamp_formats_civil_dates() {
+        let at = |secs: u64| format_utc_timestamp(UNIX_EPOCH + Duration::from_secs(secs));
+        assert_eq!(at(0), "1970-01-01T00:00:00Z");
+        assert_eq!(at(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(at(1_714_555_800), "2024-05-01T09:30:00Z");
+        assert_eq!(at(4_107_542_399), "2100-02-28T23:59:59Z");
+    }
+
+    #[test]
+    fn anchored_position_in_area_places_each_anchor() {
+        let area_pos = PhysicalPosition::new(100, 50);
+        let area_size = tauri::PhysicalSize::new(1000, 800);
+        let window_size = tauri::PhysicalSize::new(200, 100);
+        let at = |anchor| {
+            let position = anchored_position_in_area(area_pos, area_size, 10, anchor, window_size);
+            (position.x, position.y)
+        };
+        assert_eq!(at(WindowAnchor::Center), (500, 400));
+        assert_eq!(at(WindowAnchor::TopCenter), (500, 60));
+        assert_eq!(at(WindowAnchor::TopLeft), (110, 60));
+        assert_eq!(at(WindowAnchor::TopRight), (890, 60));
+        assert_eq!(at(WindowAnchor::BottomLeft), (110, 740));
+        assert_eq!(at(WindowAnchor::BottomRight), (890, 740));
+    }
+
+    #[test]
+    fn provider_request_json_sorts_by_throughput_and_adds_preferences() {
+        assert_eq!(provider_request_json(None), serde_json::json!({ "sort": "throughput" }));
+
+        let prefs: ProviderPrefs = serde_json::from_value(serde_json::json!({
+            "order": ["Anthropic"],
+            "allowFallbacks": false,
+            "dataCollection": "deny",
+        }))
+        .unwrap();
+        assert_eq!(
+            provider_request_json(Some(&prefs)),
+            serde_json::json!({
+                "sort": "throughput",
+                "order": ["Anthropic"],
+                "allow_fallbacks": false,
+                "require_parameters": false,
+                "data_collection": "deny",
+            })
+        );
+
+        let no_order = ProviderPrefs { order: Vec::new(), ..prefs };
+        assert!(provider_request_json(Some(&no_order)).get("order").is_none());
+    }
+
+    #[test]
+    fn request_overrides_validate_stop_sequences() {
+        let overrides = |stop: Vec<&str>| RequestOverrides {
+            stop: Some(stop.into_iter().map(String::from).collect()),
+            ..Default::default()
+        };
+        assert!(overrides(vec!["END", " "]).validate().is_ok());
+        assert!(overrides(vec![]).validate().is_ok());
+        assert!(overrides(vec!["a", "b", "c", "d", "e"]).validate().is_err());
+    }
+
+    #[test]
+    fn ctrl_alt_shortcuts_are_opt_in() {
+        let mut settings = default_settings();
+        assert!(is_shortcut_enabled(&settings, "focus"));
+        assert!(!is_shortcut_enabled(&settings, "scratch"));
+        assert!(!is_shortcut_enabled(&settings, "appendToChat"));
+        assert!(!is_shortcut_enabled(&settings, "clickThrough"));
+
+        settings.shortcut_enabled.insert("scratch".to_string(), true);
+        settings.shortcut_enabled.insert("focus".to_string(), false);
+        assert!(is_shortcut_enabled(&settings, "scratch"));
+        assert!(!is_shortcut_enabled(&settings, "focus"));
+    }
+
+    #[test]
+    fn parse_link_url_rejects_bad_input() {
+        assert!(parse_link_url("https://openrouter.ai", EXTERNAL_URL_SCHEMES).is_ok());
+        assert!(matches!(parse_link_url("not a url", EXTERNAL_URL_SCHEMES), Err(AppError::InvalidInput(_))));
+        assert!(matches!(parse_link_url("file:///etc/passwd", EXTERNAL_URL_SCHEMES), Err(AppError::InvalidInput(_))));
+        assert!(matches!(parse_link_url("mailto:a@b.c", PREVIEW_URL_SCHEMES), Err(AppError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn feature_shortcuts_need_their_feature() {
+        let mut settings = default_settings();
+        assert!(!is_shortcut_active(&settings, "quickReply"));
+        assert!(!is_shortcut_active(&settings, "abToggle"));
+
+        settings.quick_reply_enabled = true;
+        assert!(is_shortcut_active(&settings, "quickReply"));
+        settings.shortcut_enabled.insert("quickReply".to_string(), false);
+        assert!(!is_shortcut_active(&settings, "quickReply"));
+    }
+
+    #[test]
+    fn raise_strategy_reads_the_old_none_value() {
+        let strategy: RaiseStrategy = serde_json::from_str("\"none\"").unwrap();
+        assert_eq!(strategy, RaiseStrategy::Focus);
+        assert_eq!(serde_json::to_string(&strategy).unwrap(), "\"focus\"");
+    }
+}
@@ -11,3 +11,18 @@ export type WithoutChild<T> = T extends { child?: any } ? Omit<T, "child"> : T;
 export type WithoutChildren<T> = T extends { children?: any } ? Omit<T, "children"> : T;
 export type WithoutChildrenOrChild<T> = WithoutChildren<WithoutChild<T>>;
 export type WithElementRef<T, U extends HTMLElement = HTMLElement> = T & { ref?: U | null };
+
+/** Overrides the theme's primary color with `hex` (`#RRGGBB`), or restores it when empty. */
+export function applyAccentColor(hex: string) {
+	const root = document.documentElement.style;
+	if (!hex) {
+		root.removeProperty("--primary");
+		root.removeProperty("--primary-foreground");
+		return;
+	}
+	const [r, g, b] = [1, 3, 5].map((i) => parseInt(hex.slice(i, i + 2), 16));
+	// Dark text on light accents, light text on dark ones
+	const luminance = (0.299 * r + 0.587 * g + 0.114 * b) / 255;
+	root.setProperty("--primary", hex);
+	root.setProperty("--primary-foreground", luminance > 0.6 ? "#111111" : "#FAFAFA");
+}